@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::async_redis_queue::AsyncRedisQueue;
+use crate::periodic_task::AsyncPeriodicTask;
+
+/// Background task that periodically hands expired reservations of an [`AsyncRedisQueue`] back
+/// to the queue. It stops when it is stopped or dropped, or on the first error that retrying
+/// would not fix.
+pub struct AsyncQueueReaper {
+    task: AsyncPeriodicTask,
+}
+
+impl AsyncQueueReaper {
+    pub(crate) fn start<T>(mut redis_queue: AsyncRedisQueue<T>, interval: Duration) -> Self
+    where T: Clone + Serialize + for<'de> Deserialize<'de> + Send + 'static {
+        let task = AsyncPeriodicTask::start(interval, |ticker| async move {
+            while ticker.tick().await {
+                match redis_queue.requeue_expired().await {
+                    Err(error) if !error.is_transient() => break,
+                    _ => {}
+                }
+            }
+        });
+
+        AsyncQueueReaper { task }
+    }
+
+    pub async fn stop(self) {
+        self.task.stop().await;
+    }
+}
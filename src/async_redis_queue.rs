@@ -1,185 +1,831 @@
+use async_std::stream::StreamExt;
 use redis::aio::MultiplexedConnection;
+use redis::streams::{
+    StreamClaimReply, StreamId, StreamPendingCountReply, StreamReadOptions, StreamReadReply,
+};
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::queue_lock::async_queue_lock::AsyncQueueLock;
-use crate::queue_lock::queue_lock_builder::QueueLockBuilder;
+use crate::async_queue_reaper::AsyncQueueReaper;
+use crate::async_reserved_item::AsyncReservedItem;
+use crate::dead_letter::DeadLetter;
+use crate::error::RedisQueueError;
+use crate::queue_backend::{stale_entries_command, stream_entry_data, QueueBackend};
+use crate::queue_state::queue_clock::unix_millis;
 use crate::queue_state::queue_element::QueueElement;
+use crate::queue_state::queue_keys::{dedup_window, QueueKeys, DEFAULT_DEDUP_WINDOW};
+use crate::queue_state::queue_scripts::{
+    ack_element_script, nack_element_script, nack_stream_entry_script, peek_elements_script,
+    push_dedup_element_script, push_element_script, push_elements_script, queue_invocation,
+    requeue_dead_letter_script, requeue_expired_script, take_element_script,
+    take_elements_script,
+};
+use crate::queue_state::stream_consumer::StreamConsumer;
+
+const POP_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Clone)]
 pub struct AsyncRedisQueue<T> {
     queue_data_type: std::marker::PhantomData<T>,
     
-    queue_name: String,
+    queue_keys: QueueKeys,
+    backend: QueueBackend,
+    stream_consumer: Option<StreamConsumer>,
+    dedup_window: Duration,
+    visibility_timeout: Option<Duration>,
+    max_attempts: Option<u32>,
+    dead_letter_expired: bool,
+    inline_reaping: bool,
+    redis_client: redis::Client,
     redis_connection: MultiplexedConnection,
-
-    queue_lock_builder: QueueLockBuilder,
 }
 
 impl<T> AsyncRedisQueue<T>
 where T: Clone + Serialize + for<'de> Deserialize<'de> {
-    pub async fn new(queue_name: String, redis_client: redis::Client) -> AsyncRedisQueue<T> {
-        let queue_lock_builder = QueueLockBuilder::default()
-            .with_queue_name(queue_name.clone())
-            .with_redis_client(redis_client.clone());
-        AsyncRedisQueue {
+    pub async fn new(
+        queue_name: String,
+        redis_client: redis::Client,
+    ) -> Result<AsyncRedisQueue<T>, RedisQueueError> {
+        Ok(AsyncRedisQueue {
             queue_data_type: std::marker::PhantomData,
-            queue_name,
-            queue_lock_builder,
+            queue_keys: QueueKeys::new(&queue_name),
+            backend: QueueBackend::default(),
+            stream_consumer: None,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+            visibility_timeout: None,
+            max_attempts: None,
+            dead_letter_expired: false,
+            inline_reaping: true,
             redis_connection: redis_client
                 .get_multiplexed_async_connection()
-                .await
-                .unwrap(),
-        }
+                .await?,
+            redis_client,
+        })
     }
 
-    pub async fn push(&mut self, item: T) {
-        self.get_lock()
-            .await
-            .lock(|| async move {
-                let element = QueueElement::new(item);
+    pub fn with_backend(mut self, backend: QueueBackend) -> Self {
+        self.backend = backend;
+        self
+    }
 
-                self.push_element(element.clone()).await;
+    /// Consumer group and consumer name `pop` reads as, required by the stream backend.
+    pub fn with_consumer_group(mut self, group: String, consumer: String) -> Self {
+        self.stream_consumer = Some(StreamConsumer::new(group, consumer));
+        self
+    }
 
-                if self.get_first_element_id().await.is_none() {
-                    self.set_first_element_id(element.get_id()).await;
-                }
+    /// How long a dedup key passed to `push_with_dedup_key` is remembered, 5 minutes by default.
+    /// Windows shorter than a millisecond are rounded up to one.
+    pub fn with_dedup_window(mut self, window: Duration) -> Self {
+        self.dedup_window = dedup_window(window);
+        self
+    }
 
-                self.set_last_element_id(element.get_id()).await;
-            })
-            .await;
+    /// How long a reserved element may go unacknowledged before it is handed back to the queue.
+    pub fn with_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = Some(visibility_timeout);
+        self
     }
 
-    pub async fn pop(&mut self) -> Option<T> {
-        self.get_lock()
-            .await
-            .lock(|| async {
-                let option_element_id = self.get_first_element_id().await;
-                if option_element_id.is_none() {
-                    return None;
-                }
+    /// How many times a reserved element is delivered before it is moved to the dead letters
+    /// instead of being handed back to the queue. Fails when it is 0.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Result<Self, RedisQueueError> {
+        if max_attempts == 0 {
+            return Err(RedisQueueError::Configuration(
+                "max attempts must be at least 1".to_string(),
+            ));
+        }
+        self.max_attempts = Some(max_attempts);
+        Ok(self)
+    }
 
-                let element_id = option_element_id.unwrap();
+    /// Whether elements that expired before they were delivered are moved to the dead letters
+    /// instead of being discarded, off by default.
+    pub fn with_dead_letter_expired(mut self, dead_letter_expired: bool) -> Self {
+        self.dead_letter_expired = dead_letter_expired;
+        self
+    }
+
+    /// Whether `pop` and `reserve` requeue expired reservations themselves before taking an
+    /// element, on by default. Turn it off when a reaper runs in the background instead.
+    pub fn with_inline_reaping(mut self, inline_reaping: bool) -> Self {
+        self.inline_reaping = inline_reaping;
+        self
+    }
 
-                let option_element = self.get_element(element_id.clone()).await;
-                if option_element.is_none() {
-                    return None;
+    pub async fn push(&mut self, item: T) -> Result<(), RedisQueueError> {
+        self.push_element(QueueElement::new(item)).await
+    }
+
+    /// Pushes a batch of elements in one round trip, keeping their order, and returns their ids.
+    pub async fn push_many(
+        &mut self,
+        items: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<String>, RedisQueueError> {
+        let mut elements: Vec<QueueElement<T>> = items.into_iter().map(QueueElement::new).collect();
+        if elements.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let element_ids: Vec<String> = elements.iter().map(QueueElement::get_id).collect();
+        if self.backend == QueueBackend::LinkedList {
+            // Linked to each other here, so the script only has to link the first one in.
+            for (element, next_element_id) in elements.iter_mut().zip(&element_ids[1..]) {
+                element.set_next(next_element_id.clone());
+            }
+        }
+        let element_data = elements
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // One notification wakes every waiter, so the batch sends only one.
+        match self.backend {
+            QueueBackend::LinkedList => {
+                let mut push_elements = push_elements_script().key(self.queue_keys.first_element());
+                push_elements
+                    .key(self.queue_keys.last_element())
+                    .key(self.queue_keys.length())
+                    .arg(self.queue_keys.element_prefix())
+                    .arg(self.queue_keys.push_channel());
+                for (element_id, element_data) in element_ids.iter().zip(&element_data) {
+                    push_elements.arg(element_id).arg(element_data);
                 }
-                let first_element = option_element.unwrap();
-
-                if first_element.get_next().is_none() {
-                    self.unset_first_element_id().await;
-                    self.unset_last_element_id().await;
-                } else {
-                    let next_element_id = first_element.get_next().unwrap();
-                    self.set_first_element_id(next_element_id).await;
+                push_elements
+                    .invoke_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::List => {
+                redis::pipe()
+                    .atomic()
+                    .lpush(self.queue_keys.list(), &element_data)
+                    .ignore()
+                    .incr(self.queue_keys.length(), element_data.len())
+                    .ignore()
+                    .publish(self.queue_keys.push_channel(), &element_ids[0])
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::Stream => {
+                let mut pipe = redis::pipe();
+                for element_data in &element_data {
+                    pipe.xadd(self.queue_keys.stream(), "*", &[("element", element_data)])
+                        .ignore();
                 }
+                pipe.publish(self.queue_keys.push_channel(), &element_ids[0])
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::Priority => {
+                redis::pipe()
+                    .atomic()
+                    .lpush(self.queue_keys.priority_list(0), &element_data)
+                    .ignore()
+                    .incr(self.queue_keys.length(), element_data.len())
+                    .ignore()
+                    .zadd(self.queue_keys.priorities(), 0, 0)
+                    .ignore()
+                    .publish(self.queue_keys.push_channel(), &element_ids[0])
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+        }
+        Ok(element_ids)
+    }
 
-                self.delete_element(element_id.clone()).await;
+    /// Pushes an element ahead of every element with a lower priority, available on the priority
+    /// backend. `push` uses priority 0.
+    pub async fn push_with_priority(
+        &mut self,
+        item: T,
+        priority: i32,
+    ) -> Result<(), RedisQueueError> {
+        self.backend.require_priority("push_with_priority")?;
+        self.push_element(QueueElement::new(item).with_priority(priority))
+            .await
+    }
 
-                Some(first_element.get_data())
-            })
+    /// Pushes an element unless one was pushed with the same `dedup_key` within the dedup window,
+    /// returning the id of the element pushed for that key.
+    pub async fn push_with_dedup_key(
+        &mut self,
+        item: T,
+        dedup_key: String,
+    ) -> Result<String, RedisQueueError> {
+        let element_data = serde_json::to_string(&QueueElement::new(item))?;
+
+        // The key is only claimed once the element is in the queue, in the same script.
+        let mut push_element = queue_invocation(
+            push_dedup_element_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
+        );
+        push_element
+            .key(self.queue_keys.dedup(&dedup_key))
+            .arg(element_data)
+            .arg(self.dedup_window.as_millis() as u64);
+        Ok(push_element.invoke_async(&mut self.redis_connection).await?)
+    }
+
+    /// Pushes an element that `pop` and `reserve` skip and discard once `ttl` has passed,
+    /// instead of delivering it stale.
+    pub async fn push_with_ttl(
+        &mut self,
+        item: T,
+        ttl: Duration,
+    ) -> Result<(), RedisQueueError> {
+        self.backend.reject_stream("push_with_ttl")?;
+
+        let expires_at = unix_millis(SystemTime::now() + ttl);
+        self.push_element(QueueElement::new(item).with_expires_at(expires_at))
             .await
     }
 
-    async fn get_element(&mut self, element_id: String) -> Option<QueueElement<T>> {
-        let element_key = format!("redis-queue:{}:element:{}", self.queue_name, element_id);
+    /// Pushes an element that only becomes available after `delay`.
+    pub async fn push_delayed(&mut self, item: T, delay: Duration) -> Result<(), RedisQueueError> {
+        self.push_at(item, SystemTime::now() + delay).await
+    }
+
+    /// Pushes an element that only becomes available once `due_at` is reached, when it is moved
+    /// to the end of the queue by the next `pop` or `reserve`.
+    pub async fn push_at(&mut self, item: T, due_at: SystemTime) -> Result<(), RedisQueueError> {
+        self.backend.reject_stream("push_at")?;
+
+        let element_data = serde_json::to_string(&QueueElement::new(item))?;
+        let due_at = unix_millis(due_at);
         self.redis_connection
-            .get(element_key)
-            .await
-            .map_or(None, |data: String| serde_json::from_str(&data).ok())
+            .zadd::<String, u64, String, ()>(self.queue_keys.delayed(), element_data, due_at)
+            .await?;
+        Ok(())
     }
 
-    async fn push_element(&mut self, element: QueueElement<T>) {
-        let element_key = format!(
-            "redis-queue:{}:element:{}",
-            self.queue_name,
-            element.get_id()
+    pub async fn pop(&mut self) -> Result<Option<T>, RedisQueueError> {
+        self.reap_inline().await?;
+
+        let element_data = match self.backend {
+            QueueBackend::Stream => self.take_stream_entries(1).await?.into_iter().next(),
+            _ => self.take_element(false).await?,
+        };
+
+        match element_data {
+            Some(data) => Ok(Some(serde_json::from_str::<QueueElement<T>>(&data)?.get_data())),
+            None => Ok(None),
+        }
+    }
+
+    /// Pops up to `count` elements in one round trip, in the order they were pushed.
+    pub async fn pop_many(&mut self, count: usize) -> Result<Vec<T>, RedisQueueError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        self.reap_inline().await?;
+
+        let elements_data = match self.backend {
+            QueueBackend::Stream => self.take_stream_entries(count).await?,
+            _ => self.take_elements(count).await?,
+        };
+
+        elements_data
+            .iter()
+            .map(|data| Ok(serde_json::from_str::<QueueElement<T>>(data)?.get_data()))
+            .collect()
+    }
+
+    /// Returns the element `pop` would take next, without taking it.
+    pub async fn peek(&mut self) -> Result<Option<T>, RedisQueueError> {
+        Ok(self.peek_range(0, 1).await?.into_iter().next())
+    }
+
+    /// Returns up to `count` elements from the front of the queue, without taking them.
+    pub async fn peek_n(&mut self, count: usize) -> Result<Vec<T>, RedisQueueError> {
+        self.peek_range(0, count).await
+    }
+
+    /// Returns up to `count` elements from position `start` on, without taking them. Expired
+    /// elements are left out, like `pop` skips them, and delayed elements that are due are read
+    /// at the end of the queue, where `pop` moves them first.
+    pub async fn peek_range(
+        &mut self,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<T>, RedisQueueError> {
+        self.backend.reject_stream("peek_range")?;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut peek_elements = queue_invocation(
+            peek_elements_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
         );
-        let element_data = serde_json::to_string(&element).unwrap();
+        peek_elements
+            .arg(unix_millis(SystemTime::now()))
+            .arg(start)
+            .arg(count);
+        let elements_data: Vec<String> =
+            peek_elements.invoke_async(&mut self.redis_connection).await?;
 
-        self.redis_connection
-            .set::<String, String, String>(element_key, element_data)
-            .await
-            .unwrap();
+        elements_data
+            .iter()
+            .map(|data| Ok(serde_json::from_str::<QueueElement<T>>(data)?.get_data()))
+            .collect()
     }
 
-    async fn get_first_element_id(&mut self) -> Option<String> {
-        let first_element_key = format!("redis-queue:{}:state:first", self.queue_name);
-        self.redis_connection.get(first_element_key).await.ok()
+    /// Number of elements waiting in the queue, read from a counter every push and pop keeps up
+    /// to date. Delayed, reserved and dead-lettered elements are not counted, and expired ones are
+    /// until a pop discards them. On the stream backend it is the length of the stream, which
+    /// keeps its entries once they are acknowledged, as other consumer groups may still read them.
+    pub async fn len(&mut self) -> Result<usize, RedisQueueError> {
+        if self.backend == QueueBackend::Stream {
+            return Ok(self.redis_connection.xlen(self.queue_keys.stream()).await?);
+        }
+        let length: Option<usize> = self.redis_connection.get(self.queue_keys.length()).await?;
+        Ok(length.unwrap_or(0))
     }
 
-    async fn set_first_element_id(&mut self, element_id: String) {
-        let first_element_key = format!("redis-queue:{}:state:first", self.queue_name);
-        self.redis_connection
-            .set::<String, String, String>(first_element_key, element_id)
-            .await
-            .unwrap();
+    pub async fn is_empty(&mut self) -> Result<bool, RedisQueueError> {
+        Ok(self.len().await? == 0)
     }
 
-    async fn unset_first_element_id(&mut self) {
-        let first_element_key = format!("redis-queue:{}:state:first", self.queue_name);
-        self.redis_connection
-            .del::<String, u8>(first_element_key)
-            .await
-            .unwrap();
+    /// Takes the first element like `pop`, but keeps it in the queue's processing set until the
+    /// returned item is acknowledged, so it is not lost if the consumer fails before that. On the
+    /// stream backend the entry stays pending in the consumer group instead.
+    pub async fn reserve(&mut self) -> Result<Option<AsyncReservedItem<T>>, RedisQueueError> {
+        self.reap_inline().await?;
+
+        if self.backend == QueueBackend::Stream {
+            // Streams keep their read but unacknowledged entries pending in the consumer group.
+            let entries = self.read_stream_entries(1).await?;
+            return Ok(self.reserve_stream_entries(entries, &HashMap::new())?.into_iter().next());
+        }
+        let element_data = self.take_element(true).await?;
+
+        match element_data {
+            Some(data) => Ok(Some(AsyncReservedItem::new(
+                self.clone(),
+                serde_json::from_str(&data)?,
+            ))),
+            None => Ok(None),
+        }
     }
 
-    async fn get_last_element_id(&mut self) -> Option<String> {
-        let last_element_key = format!("redis-queue:{}:state:last", self.queue_name);
-        self.redis_connection.get(last_element_key).await.ok()
+    /// Pops the first element, waiting up to `timeout` for one to be pushed if the queue is empty.
+    pub async fn pop_wait(&mut self, timeout: Duration) -> Result<Option<T>, RedisQueueError> {
+        let deadline = Instant::now() + timeout;
+
+        // Subscribe before the first pop so a push in between is not missed.
+        let mut push_pubsub = self.redis_client.get_async_pubsub().await?;
+        push_pubsub
+            .subscribe(self.queue_keys.push_channel())
+            .await?;
+        let mut push_notifications = push_pubsub.into_on_message();
+
+        loop {
+            if let Some(item) = self.pop().await? {
+                return Ok(Some(item));
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+
+            // Delayed elements falling due are not notified, so wake up for the next one.
+            let wait = match self.next_due_in().await? {
+                Some(next_due_in) => remaining.min(next_due_in),
+                None => remaining,
+            };
+
+            // A closed subscription degrades to polling until the deadline.
+            let notification = async_std::future::timeout(wait, push_notifications.next()).await;
+            if let Ok(None) = notification {
+                async_std::task::sleep(wait.min(POP_WAIT_POLL_INTERVAL)).await;
+            }
+        }
+    }
+
+    /// Hands every reserved element whose visibility timeout has passed back to the front of the
+    /// queue, or to the dead letters, returning how many were taken back.
+    pub async fn requeue_expired(&mut self) -> Result<usize, RedisQueueError> {
+        self.backend.reject_stream("requeue_expired")?;
+
+        let mut requeue_expired = queue_invocation(
+            requeue_expired_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
+        );
+        requeue_expired.arg(unix_millis(SystemTime::now()));
+        Ok(requeue_expired.invoke_async(&mut self.redis_connection).await?)
     }
 
-    async fn set_last_element_id(&mut self, element_id: String) {
-        let current_last_element_id = self.get_last_element_id().await;
-        if current_last_element_id.is_some() {
-            let current_last_element_id = current_last_element_id.unwrap();
-            let mut current_last_element = self
-                .get_element(current_last_element_id)
-                .await
-                .unwrap();
-            current_last_element.set_next(Some(element_id.clone()));
-            self.update_element(current_last_element).await;
+    /// Elements that used up their delivery attempts, in no particular order.
+    pub async fn dead_letters(&mut self) -> Result<Vec<DeadLetter<T>>, RedisQueueError> {
+        self.backend.reject_stream("dead_letters")?;
+
+        let dead_letters: Vec<String> = self
+            .redis_connection
+            .hvals(self.queue_keys.dead_letters())
+            .await?;
+        dead_letters
+            .iter()
+            .map(|data| Ok(DeadLetter::new(serde_json::from_str(data)?)))
+            .collect()
+    }
+
+    pub async fn dead_letter(
+        &mut self,
+        element_id: String,
+    ) -> Result<Option<DeadLetter<T>>, RedisQueueError> {
+        self.backend.reject_stream("dead_letter")?;
+
+        let dead_letter: Option<String> = self
+            .redis_connection
+            .hget(self.queue_keys.dead_letters(), element_id)
+            .await?;
+        match dead_letter {
+            Some(data) => Ok(Some(DeadLetter::new(serde_json::from_str(&data)?))),
+            None => Ok(None),
         }
+    }
 
-        let last_element_key = format!("redis-queue:{}:state:last", self.queue_name);
-        self.redis_connection
-            .set::<String, String, String>(last_element_key, element_id)
-            .await
-            .unwrap();
+    /// Moves a dead letter to the end of the queue with its delivery attempts reset, `false` when
+    /// there is no dead letter with that id.
+    pub async fn requeue_dead_letter(
+        &mut self,
+        element_id: String,
+    ) -> Result<bool, RedisQueueError> {
+        self.backend.reject_stream("requeue_dead_letter")?;
+
+        let mut requeue_dead_letter = queue_invocation(
+            requeue_dead_letter_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
+        );
+        requeue_dead_letter.arg(element_id);
+        Ok(requeue_dead_letter
+            .invoke_async::<_, u32>(&mut self.redis_connection)
+            .await? == 1)
     }
 
-    async fn unset_last_element_id(&mut self) {
-        let last_element_key = format!("redis-queue:{}:state:last", self.queue_name);
-        self.redis_connection
-            .del::<String, u8>(last_element_key)
-            .await
-            .unwrap();
+    /// Deletes every dead letter, returning how many there were.
+    pub async fn purge_dead_letters(&mut self) -> Result<usize, RedisQueueError> {
+        self.backend.reject_stream("purge_dead_letters")?;
+
+        let (purged,): (usize,) = redis::pipe()
+            .atomic()
+            .hlen(self.queue_keys.dead_letters())
+            .del(self.queue_keys.dead_letters())
+            .ignore()
+            .query_async(&mut self.redis_connection)
+            .await?;
+        Ok(purged)
     }
 
-    async fn delete_element(&mut self, element_id: String) {
-        let element_key = format!("redis-queue:{}:element:{}", self.queue_name, element_id);
-        self.redis_connection
-            .del::<String, u8>(element_key)
-            .await
-            .unwrap();
+    /// Runs `requeue_expired` every `interval` on a background task until the returned reaper
+    /// is stopped or dropped, or a pass fails with an error other than a lost connection. Fails
+    /// up front on the stream backend, where expired entries are claimed instead.
+    pub fn start_reaper(&self, interval: Duration) -> Result<AsyncQueueReaper, RedisQueueError>
+    where T: Send + 'static {
+        self.backend.reject_stream("start_reaper")?;
+        Ok(AsyncQueueReaper::start(self.clone(), interval))
+    }
+
+    /// Creates a consumer group that reads the stream from its first entry. Creating a group
+    /// that already exists does nothing.
+    pub async fn create_group(&mut self, group: String) -> Result<(), RedisQueueError> {
+        self.backend.require_stream("create_group")?;
+
+        let created: redis::RedisResult<()> = self
+            .redis_connection
+            .xgroup_create_mkstream(self.queue_keys.stream(), group, "0")
+            .await;
+        match created {
+            Err(err) if err.code() == Some("BUSYGROUP") => Ok(()),
+            created => Ok(created?),
+        }
+    }
+
+    /// Takes over up to `count` entries that other consumers of the group read but did not
+    /// acknowledge within `min_idle`, and reserves them for this one until they are acked.
+    pub async fn claim_stale(
+        &mut self,
+        min_idle: Duration,
+        count: usize,
+    ) -> Result<Vec<AsyncReservedItem<T>>, RedisQueueError> {
+        let stream_consumer = self.stream_consumer()?;
+        let min_idle_time = min_idle.as_millis() as usize;
+
+        // Only entries idle for `min_idle` are listed, so fresh ones cannot crowd out stale ones.
+        let pending: StreamPendingCountReply = stale_entries_command(
+            self.queue_keys.stream(),
+            stream_consumer.get_group(),
+            min_idle_time,
+            count,
+        )
+        .query_async(&mut self.redis_connection)
+        .await?;
+        if pending.ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let stale_ids: Vec<&String> = pending.ids.iter().map(|pending_id| &pending_id.id).collect();
+        let deliveries: HashMap<String, usize> = pending
+            .ids
+            .iter()
+            .map(|pending_id| (pending_id.id.clone(), pending_id.times_delivered))
+            .collect();
+
+        // XCLAIM checks the idle time again, skipping entries another consumer claimed meanwhile.
+        let claimed: StreamClaimReply = self
+            .redis_connection
+            .xclaim(
+                self.queue_keys.stream(),
+                stream_consumer.get_group(),
+                stream_consumer.get_consumer(),
+                min_idle_time,
+                &stale_ids,
+            )
+            .await?;
+
+        self.reserve_stream_entries(claimed.ids, &deliveries)
+    }
+
+    pub(crate) async fn ack_element(
+        &mut self,
+        element_id: String,
+        redeliveries: u32,
+    ) -> Result<(), RedisQueueError> {
+        match ack_element_script()
+            .key(self.queue_keys.processing())
+            .key(self.queue_keys.processing_deadlines())
+            .arg(&element_id)
+            .arg(redeliveries)
+            .invoke_async::<_, u32>(&mut self.redis_connection)
+            .await?
+        {
+            0 => Err(RedisQueueError::ReservationLost(element_id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Acknowledges a stream entry taken with `reserve` or `claim_stale`.
+    pub(crate) async fn ack_stream_entry(
+        &mut self,
+        entry_id: String,
+        element_id: String,
+    ) -> Result<(), RedisQueueError> {
+        let stream_consumer = self.stream_consumer()?;
+        match self
+            .redis_connection
+            .xack::<String, String, String, u32>(
+                self.queue_keys.stream(),
+                stream_consumer.get_group(),
+                &[entry_id],
+            )
+            .await?
+        {
+            0 => Err(RedisQueueError::ReservationLost(element_id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Hands a stream entry taken with `reserve` or `claim_stale` back, at the end of the stream.
+    pub(crate) async fn nack_stream_entry(
+        &mut self,
+        entry_id: String,
+        element: QueueElement<T>,
+    ) -> Result<(), RedisQueueError> {
+        let stream_consumer = self.stream_consumer()?;
+        let element_id = element.get_id();
+        let redeliveries = element.get_redeliveries() + 1;
+        let element_data = serde_json::to_string(&element.with_redeliveries(redeliveries))?;
+
+        let mut nack_stream_entry = nack_stream_entry_script().key(self.queue_keys.stream());
+        nack_stream_entry
+            .arg(stream_consumer.get_group())
+            .arg(entry_id)
+            .arg(element_data)
+            .arg(self.queue_keys.push_channel());
+        match nack_stream_entry.invoke_async::<_, u32>(&mut self.redis_connection).await? {
+            0 => Err(RedisQueueError::ReservationLost(element_id)),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) async fn nack_element(
+        &mut self,
+        element_id: String,
+        redeliveries: u32,
+    ) -> Result<(), RedisQueueError> {
+        let mut nack_element = queue_invocation(
+            nack_element_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
+        );
+        nack_element.arg(&element_id).arg(redeliveries);
+
+        match nack_element
+            .invoke_async::<_, u32>(&mut self.redis_connection)
+            .await?
+        {
+            0 => Err(RedisQueueError::ReservationLost(element_id)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn push_element(&mut self, element: QueueElement<T>) -> Result<(), RedisQueueError> {
+        let element_data = serde_json::to_string(&element)?;
+
+        // Every backend notifies `pop_wait` waiters in the same round trip as the push.
+        match self.backend {
+            QueueBackend::LinkedList => {
+                push_element_script()
+                    .key(self.queue_keys.first_element())
+                    .key(self.queue_keys.last_element())
+                    .key(self.queue_keys.length())
+                    .arg(self.queue_keys.element_prefix())
+                    .arg(element_data)
+                    .arg(self.queue_keys.push_channel())
+                    .invoke_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::List => {
+                redis::pipe()
+                    .atomic()
+                    .lpush(self.queue_keys.list(), element_data)
+                    .ignore()
+                    .incr(self.queue_keys.length(), 1)
+                    .ignore()
+                    .publish(self.queue_keys.push_channel(), element.get_id())
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::Stream => {
+                redis::pipe()
+                    .xadd(self.queue_keys.stream(), "*", &[("element", element_data)])
+                    .ignore()
+                    .publish(self.queue_keys.push_channel(), element.get_id())
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+            QueueBackend::Priority => {
+                redis::pipe()
+                    .atomic()
+                    .lpush(self.queue_keys.priority_list(element.get_priority()), element_data)
+                    .ignore()
+                    .incr(self.queue_keys.length(), 1)
+                    .ignore()
+                    .zadd(
+                        self.queue_keys.priorities(),
+                        element.get_priority(),
+                        element.get_priority(),
+                    )
+                    .ignore()
+                    .publish(self.queue_keys.push_channel(), element.get_id())
+                    .ignore()
+                    .query_async::<_, ()>(&mut self.redis_connection)
+                    .await?
+            }
+        }
+        Ok(())
+    }
+
+    /// How long until the next delayed element is due, if there is one.
+    async fn next_due_in(&mut self) -> Result<Option<Duration>, RedisQueueError> {
+        let next_due: Vec<(String, f64)> = self
+            .redis_connection
+            .zrange_withscores(self.queue_keys.delayed(), 0, 0)
+            .await?;
+        let now = unix_millis(SystemTime::now());
+        Ok(next_due
+            .first()
+            .map(|(_, due_at)| Duration::from_millis((*due_at as u64).saturating_sub(now))))
     }
 
-    async fn update_element(&mut self, element: QueueElement<T>) {
-        let element_key = format!(
-            "redis-queue:{}:element:{}",
-            self.queue_name,
-            element.get_id()
+    /// Takes the first element off the queue, promoting the delayed elements that are due first.
+    async fn take_element(&mut self, reserve: bool) -> Result<Option<String>, RedisQueueError> {
+        let mut take_element = queue_invocation(
+            take_element_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
         );
-        let element_data = serde_json::to_string(&element).unwrap();
+        let redelivery_deadline = match (reserve, self.visibility_timeout) {
+            (true, Some(visibility_timeout)) => {
+                unix_millis(SystemTime::now() + visibility_timeout).to_string()
+            }
+            _ => String::new(),
+        };
+        take_element
+            .arg(unix_millis(SystemTime::now()))
+            .arg(if reserve { "1" } else { "0" })
+            .arg(redelivery_deadline)
+            .arg(if self.dead_letter_expired { "1" } else { "0" });
+        Ok(take_element.invoke_async(&mut self.redis_connection).await?)
+    }
 
+    async fn take_elements(&mut self, count: usize) -> Result<Vec<String>, RedisQueueError> {
+        let mut take_elements = queue_invocation(
+            take_elements_script(),
+            &self.queue_keys,
+            self.backend,
+            self.max_attempts,
+        );
+        take_elements
+            .arg(unix_millis(SystemTime::now()))
+            .arg(if self.dead_letter_expired { "1" } else { "0" })
+            .arg(count);
+        Ok(take_elements.invoke_async(&mut self.redis_connection).await?)
+    }
+
+    async fn reap_inline(&mut self) -> Result<(), RedisQueueError> {
+        if self.inline_reaping
+            && self.visibility_timeout.is_some()
+            && self.backend != QueueBackend::Stream
+        {
+            self.requeue_expired().await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next entries for this consumer. They stay pending in the consumer group until
+    /// they are acknowledged, and can be taken over with `claim_stale` if this consumer dies first.
+    async fn read_stream_entries(
+        &mut self,
+        count: usize,
+    ) -> Result<Vec<StreamId>, RedisQueueError> {
+        let stream_consumer = self.stream_consumer()?;
+
+        let read_options = StreamReadOptions::default()
+            .group(stream_consumer.get_group(), stream_consumer.get_consumer())
+            .count(count);
+        let read: Option<StreamReadReply> = self
+            .redis_connection
+            .xread_options(&[self.queue_keys.stream()], &[">"], &read_options)
+            .await?;
+        Ok(read
+            .map(|read| read.keys.into_iter().flat_map(|stream| stream.ids).collect())
+            .unwrap_or_default())
+    }
+
+    /// Reads the next entries for this consumer and acknowledges them right away, for `pop`.
+    async fn take_stream_entries(&mut self, count: usize) -> Result<Vec<String>, RedisQueueError> {
+        let entries = self.read_stream_entries(count).await?;
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let stream_consumer = self.stream_consumer()?;
+        let entry_ids: Vec<&String> = entries.iter().map(|entry| &entry.id).collect();
         self.redis_connection
-            .set::<String, String, String>(element_key, element_data)
-            .await
-            .unwrap();
+            .xack::<String, String, &String, ()>(
+                self.queue_keys.stream(),
+                stream_consumer.get_group(),
+                &entry_ids,
+            )
+            .await?;
+
+        entries.iter().map(stream_entry_data).collect()
+    }
+
+    /// Wraps stream entries left pending for this consumer, counting the deliveries of the entry
+    /// before this one as redeliveries.
+    fn reserve_stream_entries(
+        &self,
+        entries: Vec<StreamId>,
+        deliveries: &HashMap<String, usize>,
+    ) -> Result<Vec<AsyncReservedItem<T>>, RedisQueueError> {
+        entries
+            .into_iter()
+            .map(|entry| {
+                let element = serde_json::from_str::<QueueElement<T>>(&stream_entry_data(&entry)?)?;
+                // An element handed back with `nack` carries the redeliveries of its earlier entry.
+                let redeliveries = element.get_redeliveries()
+                    + deliveries.get(&entry.id).copied().unwrap_or_default() as u32;
+                let element = element.with_redeliveries(redeliveries);
+                Ok(AsyncReservedItem::new(self.clone(), element).with_stream_entry_id(entry.id))
+            })
+            .collect()
     }
 
-    async fn get_lock(&self) -> AsyncQueueLock {
-        self.queue_lock_builder.clone().async_build().await
+    fn stream_consumer(&self) -> Result<StreamConsumer, RedisQueueError> {
+        self.backend.require_stream("reading a stream")?;
+        self.stream_consumer.clone().ok_or_else(|| {
+            RedisQueueError::Configuration(
+                "a consumer group is required to read from a stream queue".to_string(),
+            )
+        })
     }
 }
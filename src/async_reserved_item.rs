@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::async_redis_queue::AsyncRedisQueue;
+use crate::error::RedisQueueError;
+use crate::queue_state::queue_element::QueueElement;
+
+/// An element taken from an [`AsyncRedisQueue`] with `reserve`, kept in the queue's processing
+/// set until it is acknowledged or handed back. On the stream backend it is an entry left pending
+/// in the consumer group instead.
+pub struct AsyncReservedItem<T: Clone + Serialize> {
+    redis_queue: AsyncRedisQueue<T>,
+    element: QueueElement<T>,
+    stream_entry_id: Option<String>,
+}
+
+impl<T> AsyncReservedItem<T>
+where T: Clone + Serialize + for<'de> Deserialize<'de> {
+    pub(crate) fn new(redis_queue: AsyncRedisQueue<T>, element: QueueElement<T>) -> Self {
+        AsyncReservedItem {
+            redis_queue,
+            element,
+            stream_entry_id: None,
+        }
+    }
+
+    /// Marks the item as a stream entry left pending in the queue's consumer group.
+    pub(crate) fn with_stream_entry_id(mut self, stream_entry_id: String) -> Self {
+        self.stream_entry_id = Some(stream_entry_id);
+        self
+    }
+
+    pub fn get_id(&self) -> String {
+        self.element.get_id()
+    }
+
+    pub fn get_data(&self) -> T {
+        self.element.get_data()
+    }
+
+    /// How many times the element was handed back to the queue before this delivery.
+    pub fn get_redeliveries(&self) -> u32 {
+        self.element.get_redeliveries()
+    }
+
+    /// Marks the element as processed, removing it for good.
+    pub async fn ack(mut self) -> Result<(), RedisQueueError> {
+        match self.stream_entry_id.take() {
+            Some(stream_entry_id) => {
+                self.redis_queue
+                    .ack_stream_entry(stream_entry_id, self.element.get_id())
+                    .await
+            }
+            None => {
+                self.redis_queue
+                    .ack_element(self.element.get_id(), self.element.get_redeliveries())
+                    .await
+            }
+        }
+    }
+
+    /// Returns the element to the front of the queue so it is delivered again. On the stream
+    /// backend it is added again at the end of the stream, and the entry is acknowledged.
+    pub async fn nack(mut self) -> Result<(), RedisQueueError> {
+        match self.stream_entry_id.take() {
+            Some(stream_entry_id) => {
+                self.redis_queue
+                    .nack_stream_entry(stream_entry_id, self.element)
+                    .await
+            }
+            None => {
+                self.redis_queue
+                    .nack_element(self.element.get_id(), self.element.get_redeliveries())
+                    .await
+            }
+        }
+    }
+}
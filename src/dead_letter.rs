@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::queue_state::queue_element::QueueElement;
+
+/// Why an element was moved to the dead letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// It was delivered `max_attempts` times without being acknowledged.
+    AttemptsExhausted,
+    /// Its TTL ran out before it was delivered, with `with_dead_letter_expired(true)`.
+    Expired,
+}
+
+/// An element that used up its delivery attempts or expired, kept in the queue's dead letters until
+/// it is requeued or purged.
+pub struct DeadLetter<T: Clone + Serialize> {
+    element: QueueElement<T>,
+}
+
+impl<T> DeadLetter<T>
+where T: Clone + Serialize + for<'de> Deserialize<'de> {
+    pub(crate) fn new(element: QueueElement<T>) -> Self {
+        DeadLetter { element }
+    }
+
+    pub fn get_id(&self) -> String {
+        self.element.get_id()
+    }
+
+    pub fn get_data(&self) -> T {
+        self.element.get_data()
+    }
+
+    pub fn get_reason(&self) -> DeadLetterReason {
+        if self.element.is_expired() {
+            DeadLetterReason::Expired
+        } else {
+            DeadLetterReason::AttemptsExhausted
+        }
+    }
+
+    /// How many times the element was delivered before it was dead-lettered. An expired element
+    /// was only delivered as often as it was handed back, none at all if it never was.
+    pub fn get_attempts(&self) -> u32 {
+        match self.get_reason() {
+            DeadLetterReason::AttemptsExhausted => self.element.get_redeliveries() + 1,
+            DeadLetterReason::Expired => self.element.get_redeliveries(),
+        }
+    }
+
+    /// When the element expired, if it was pushed with a TTL.
+    pub fn get_expires_at(&self) -> Option<SystemTime> {
+        self.element
+            .get_expires_at()
+            .map(|expires_at| UNIX_EPOCH + Duration::from_millis(expires_at))
+    }
+}
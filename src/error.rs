@@ -0,0 +1,84 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RedisQueueError {
+    Connection(redis::RedisError),
+    Serialization(serde_json::Error),
+    LockTimeout(String),
+    LockLost(String),
+    CorruptedState(String),
+    Configuration(String),
+    Unsupported(String),
+    ReservationLost(String),
+}
+
+impl RedisQueueError {
+    /// Whether the same call may succeed when retried, because the connection to Redis failed
+    /// rather than the call itself.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RedisQueueError::Connection(err) => {
+                err.is_io_error()
+                    || err.is_timeout()
+                    || err.is_connection_dropped()
+                    || err.is_connection_refusal()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RedisQueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedisQueueError::Connection(err) => write!(f, "redis connection error: {}", err),
+            RedisQueueError::Serialization(err) => write!(f, "serialization error: {}", err),
+            RedisQueueError::LockTimeout(lock_name) => {
+                write!(f, "timed out waiting for lock {}", lock_name)
+            }
+            RedisQueueError::LockLost(lock_name) => {
+                write!(f, "lock {} was lost before it was released", lock_name)
+            }
+            RedisQueueError::CorruptedState(message) => {
+                write!(f, "corrupted queue state: {}", message)
+            }
+            RedisQueueError::Configuration(message) => {
+                write!(f, "invalid configuration: {}", message)
+            }
+            RedisQueueError::Unsupported(message) => {
+                write!(f, "unsupported operation: {}", message)
+            }
+            RedisQueueError::ReservationLost(element_id) => {
+                write!(f, "reservation of element {} expired before it was acked", element_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RedisQueueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RedisQueueError::Connection(err) => Some(err),
+            RedisQueueError::Serialization(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<redis::RedisError> for RedisQueueError {
+    fn from(err: redis::RedisError) -> Self {
+        // The queue scripts report an inconsistent queue with a `CORRUPTED` error reply.
+        match err.code() {
+            Some("CORRUPTED") => {
+                RedisQueueError::CorruptedState(err.detail().unwrap_or_default().to_string())
+            }
+            _ => RedisQueueError::Connection(err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for RedisQueueError {
+    fn from(err: serde_json::Error) -> Self {
+        RedisQueueError::Serialization(err)
+    }
+}
@@ -1,14 +1,30 @@
+pub mod async_queue_reaper;
 pub mod async_redis_queue;
-mod queue_lock;
+pub mod async_reserved_item;
+pub mod dead_letter;
+pub mod error;
+mod periodic_task;
+pub mod queue_backend;
+pub mod queue_lock;
+pub mod queue_reaper;
 mod queue_state;
 pub mod redis_queue;
+pub mod reserved_item;
 mod test_utils;
 
 #[cfg(test)]
 mod tests {
     use crate::async_redis_queue::AsyncRedisQueue;
+    use crate::dead_letter::DeadLetterReason;
+    use crate::error::RedisQueueError;
+    use crate::queue_backend::QueueBackend;
     use crate::redis_queue::RedisQueue;
-    use crate::test_utils::init_redis::initialize_redis_client;
+    use crate::test_utils::init_redis::{
+        initialize_async_redis, initialize_redis, initialize_redis_client,
+    };
+    use redis::streams::{StreamReadOptions, StreamReadReply};
+    use redis::{AsyncCommands, Commands};
+    use std::time::{Duration, SystemTime};
 
     #[test]
     fn initialize_redis_queue() {
@@ -27,11 +43,982 @@ mod tests {
 
         let item = "test".to_string();
 
-        redis_queue.push(item.clone());
-        let result = redis_queue.pop().unwrap();
+        redis_queue.push(item.clone()).unwrap();
+        let result = redis_queue.pop().unwrap().unwrap();
         assert_eq!(result, item);
     }
 
+    #[test]
+    fn test_redis_queue_keeps_order() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_keeps_order".to_string(),
+            initialize_redis_client(),
+        );
+
+        for item in 0..5 {
+            redis_queue.push(item).unwrap();
+        }
+        for item in 0..5 {
+            assert_eq!(redis_queue.pop().unwrap(), Some(item));
+        }
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_keeps_data_intact() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_keeps_data_intact".to_string(),
+            initialize_redis_client(),
+        );
+        let item = (u64::MAX, Vec::<u8>::new(), 0.1_f64, "a/b".to_string());
+
+        // The first element is relinked by the push of the second one.
+        redis_queue.push(item.clone()).unwrap();
+        redis_queue.push(item.clone()).unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some(item.clone()));
+        assert_eq!(redis_queue.pop().unwrap(), Some(item));
+    }
+
+    #[test]
+    fn test_redis_queue_with_missing_first_element() {
+        let mut redis_connection = initialize_redis();
+        let mut redis_queue: RedisQueue<String> = RedisQueue::new(
+            "test_redis_queue_with_missing_first_element".to_string(),
+            initialize_redis_client(),
+        );
+
+        let _: () = redis_connection
+            .set(
+                "redis-queue:test_redis_queue_with_missing_first_element:state:first",
+                "missing-element",
+            )
+            .unwrap();
+
+        let result = redis_queue.pop();
+        assert!(matches!(result, Err(RedisQueueError::CorruptedState(_))));
+    }
+
+    #[test]
+    fn test_redis_queue_with_list_backend() {
+        let mut redis_connection = initialize_redis();
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_with_list_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List);
+
+        for item in 0..5 {
+            redis_queue.push(item).unwrap();
+        }
+        let list_length: usize = redis_connection
+            .llen("redis-queue:test_redis_queue_with_list_backend:list")
+            .unwrap();
+        assert_eq!(list_length, 5);
+
+        for item in 0..5 {
+            assert_eq!(redis_queue.pop().unwrap(), Some(item));
+        }
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_with_stream_backend() {
+        let mut redis_connection = initialize_redis();
+        let _: () = redis_connection
+            .del("redis-queue:test_redis_queue_with_stream_backend:stream")
+            .unwrap();
+
+        let redis_queue = RedisQueue::new(
+            "test_redis_queue_with_stream_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream);
+        let mut first_group = redis_queue
+            .clone()
+            .with_consumer_group("first-group".to_string(), "consumer".to_string());
+        let mut second_group = redis_queue
+            .with_consumer_group("second-group".to_string(), "consumer".to_string());
+        first_group.create_group("first-group".to_string()).unwrap();
+        second_group.create_group("second-group".to_string()).unwrap();
+        first_group.create_group("first-group".to_string()).unwrap();
+
+        for item in 0..3 {
+            first_group.push(item).unwrap();
+        }
+        assert_eq!(first_group.len().unwrap(), 3);
+
+        // Every group reads each element once.
+        for redis_queue in [&mut first_group, &mut second_group] {
+            for item in 0..3 {
+                assert_eq!(redis_queue.pop().unwrap(), Some(item));
+            }
+            assert_eq!(redis_queue.pop().unwrap(), None);
+        }
+        assert_eq!(second_group.len().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_redis_queue_claims_stale_stream_entries() {
+        let mut redis_connection = initialize_redis();
+        let stream_name = "redis-queue:test_redis_queue_claims_stale_stream_entries:stream";
+        let _: () = redis_connection.del(stream_name).unwrap();
+
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_claims_stale_stream_entries".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "consumer".to_string());
+        redis_queue.create_group("group".to_string()).unwrap();
+        redis_queue.push("test".to_string()).unwrap();
+
+        // Another consumer reads the entry and dies before acknowledging it.
+        let read_options = StreamReadOptions::default()
+            .group("group", "crashed-consumer")
+            .count(1);
+        let _: StreamReadReply = redis_connection
+            .xread_options(&[stream_name], &[">"], &read_options)
+            .unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        let claimed = redis_queue.claim_stale(Duration::from_secs(60), 10).unwrap();
+        assert!(claimed.is_empty());
+
+        std::thread::sleep(Duration::from_millis(200));
+        let mut claimed = redis_queue.claim_stale(Duration::from_millis(100), 10).unwrap();
+        assert_eq!(claimed.len(), 1);
+        let reserved_item = claimed.pop().unwrap();
+        assert_eq!(reserved_item.get_data(), "test".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+
+        // The claimed entry stays pending until it is acknowledged.
+        std::thread::sleep(Duration::from_millis(200));
+        let claimed = redis_queue.claim_stale(Duration::from_millis(100), 10).unwrap();
+        assert_eq!(claimed.len(), 1);
+        for claimed_item in claimed {
+            claimed_item.ack().unwrap();
+        }
+        let result = reserved_item.ack();
+        assert!(matches!(result, Err(RedisQueueError::ReservationLost(_))));
+
+        let claimed = redis_queue.claim_stale(Duration::from_millis(0), 10).unwrap();
+        assert!(claimed.is_empty());
+    }
+
+    #[test]
+    fn test_redis_queue_claims_stale_stream_entries_behind_fresh_ones() {
+        let mut redis_connection = initialize_redis();
+        let stream_name =
+            "redis-queue:test_redis_queue_claims_stale_stream_entries_behind_fresh_ones:stream";
+        let _: () = redis_connection.del(stream_name).unwrap();
+
+        let stream_queue = |consumer: &str| {
+            RedisQueue::new(
+                "test_redis_queue_claims_stale_stream_entries_behind_fresh_ones".to_string(),
+                initialize_redis_client(),
+            )
+            .with_backend(QueueBackend::Stream)
+            .with_consumer_group("group".to_string(), consumer.to_string())
+        };
+        let mut redis_queue = stream_queue("consumer");
+        redis_queue.create_group("group".to_string()).unwrap();
+        for item in 0..4 {
+            redis_queue.push(item).unwrap();
+        }
+
+        // A consumer reads every entry and dies, then another one takes over the first three.
+        let read_options = StreamReadOptions::default()
+            .group("group", "crashed-consumer")
+            .count(4);
+        let _: StreamReadReply = redis_connection
+            .xread_options(&[stream_name], &[">"], &read_options)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let claimed = stream_queue("other-consumer")
+            .claim_stale(Duration::from_millis(100), 3)
+            .unwrap();
+        let claimed_items: Vec<i32> = claimed.iter().map(|item| item.get_data()).collect();
+        assert_eq!(claimed_items, vec![0, 1, 2]);
+
+        // The freshly claimed entries come first in the stream but are not stale.
+        let claimed = redis_queue.claim_stale(Duration::from_millis(100), 1).unwrap();
+        let claimed_items: Vec<i32> = claimed.iter().map(|item| item.get_data()).collect();
+        assert_eq!(claimed_items, vec![3]);
+    }
+
+    #[test]
+    fn test_redis_queue_reserve_and_ack_stream_entries() {
+        let mut redis_connection = initialize_redis();
+        let stream_name = "redis-queue:test_redis_queue_reserve_and_ack_stream_entries:stream";
+        let _: () = redis_connection.del(stream_name).unwrap();
+
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_reserve_and_ack_stream_entries".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "consumer".to_string());
+        redis_queue.create_group("group".to_string()).unwrap();
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push("second".to_string()).unwrap();
+
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "first".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 0);
+        reserved_item.ack().unwrap();
+
+        // A consumer that dies before acknowledging leaves the entry for another one to claim.
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "second".to_string());
+        drop(reserved_item);
+        assert!(redis_queue.reserve().unwrap().is_none());
+
+        let mut other_consumer: RedisQueue<String> = RedisQueue::new(
+            "test_redis_queue_reserve_and_ack_stream_entries".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "other-consumer".to_string());
+        std::thread::sleep(Duration::from_millis(200));
+        let mut claimed = other_consumer.claim_stale(Duration::from_millis(100), 10).unwrap();
+        assert_eq!(claimed.len(), 1);
+        let reserved_item = claimed.pop().unwrap();
+        assert_eq!(reserved_item.get_data(), "second".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+        reserved_item.ack().unwrap();
+
+        let claimed = other_consumer.claim_stale(Duration::from_millis(0), 10).unwrap();
+        assert!(claimed.is_empty());
+    }
+
+    #[test]
+    fn test_redis_queue_nack_stream_entries() {
+        let mut redis_connection = initialize_redis();
+        let stream_name = "redis-queue:test_redis_queue_nack_stream_entries:stream";
+        let _: () = redis_connection.del(stream_name).unwrap();
+
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_nack_stream_entries".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "consumer".to_string());
+        redis_queue.create_group("group".to_string()).unwrap();
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push("second".to_string()).unwrap();
+
+        // A handed back entry is delivered again after the ones already in the stream.
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "second".to_string());
+        reserved_item.ack().unwrap();
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "first".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+        reserved_item.nack().unwrap();
+
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_redeliveries(), 2);
+        reserved_item.ack().unwrap();
+        assert!(redis_queue.reserve().unwrap().is_none());
+
+        // Nothing is left pending for another consumer to claim.
+        std::thread::sleep(Duration::from_millis(20));
+        let claimed = redis_queue.claim_stale(Duration::from_millis(0), 10).unwrap();
+        assert!(claimed.is_empty());
+    }
+
+    #[test]
+    fn test_redis_queue_stream_operations_require_stream_backend() {
+        let mut redis_queue: RedisQueue<String> = RedisQueue::new(
+            "test_redis_queue_stream_operations_require_stream_backend".to_string(),
+            initialize_redis_client(),
+        );
+        let result = redis_queue.create_group("group".to_string());
+        assert!(matches!(result, Err(RedisQueueError::Unsupported(_))));
+
+        let mut redis_queue = redis_queue.with_backend(QueueBackend::Stream);
+        let result = redis_queue.pop();
+        assert!(matches!(result, Err(RedisQueueError::Configuration(_))));
+
+        let result = redis_queue.reserve();
+        assert!(matches!(result, Err(RedisQueueError::Configuration(_))));
+
+        let result = redis_queue.push_delayed("test".to_string(), Duration::from_secs(1));
+        assert!(matches!(result, Err(RedisQueueError::Unsupported(_))));
+
+        let result = redis_queue.start_reaper(Duration::from_millis(50));
+        assert!(matches!(result, Err(RedisQueueError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_redis_queue_pop_blocking_times_out() {
+        let mut redis_queue: RedisQueue<String> = RedisQueue::new(
+            "test_redis_queue_pop_blocking_times_out".to_string(),
+            initialize_redis_client(),
+        );
+
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_blocking(Duration::from_millis(300)).unwrap();
+        assert_eq!(result, None);
+        assert!(started_at.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_redis_queue_pop_blocking_woken_by_push() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_pop_blocking_woken_by_push".to_string(),
+            initialize_redis_client(),
+        );
+
+        let mut producer = redis_queue.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            producer.push("test".to_string()).unwrap();
+        });
+
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_blocking(Duration::from_secs(10)).unwrap();
+        assert_eq!(result, Some("test".to_string()));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_redis_queue_reserve_and_ack() {
+        let mut redis_connection = initialize_redis();
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_reserve_and_ack".to_string(),
+            initialize_redis_client(),
+        );
+        let processing = "redis-queue:test_redis_queue_reserve_and_ack:processing";
+
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push("second".to_string()).unwrap();
+
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "first".to_string());
+        let in_flight: usize = redis_connection.hlen(processing).unwrap();
+        assert_eq!(in_flight, 1);
+
+        reserved_item.ack().unwrap();
+        let in_flight: usize = redis_connection.hlen(processing).unwrap();
+        assert_eq!(in_flight, 0);
+
+        assert_eq!(redis_queue.pop().unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_reserve_and_nack() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_reserve_and_nack".to_string(),
+            initialize_redis_client(),
+        );
+
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push("second".to_string()).unwrap();
+
+        // A handed back element is delivered again before the rest of the queue.
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+
+        // Also when it is the only element left.
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        redis_queue.push("third".to_string()).unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("third".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_reserve_with_list_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_reserve_with_list_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List);
+
+        redis_queue.push(0).unwrap();
+        redis_queue.push(1).unwrap();
+
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), 0);
+        reserved_item.ack().unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some(1));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_visibility_timeout() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_visibility_timeout".to_string(),
+            initialize_redis_client(),
+        )
+        .with_visibility_timeout(Duration::from_millis(100));
+
+        redis_queue.push("test".to_string()).unwrap();
+
+        let expired_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(expired_item.get_redeliveries(), 0);
+        assert!(redis_queue.reserve().unwrap().is_none());
+
+        // The next reserve requeues the expired element before taking one.
+        std::thread::sleep(Duration::from_millis(200));
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "test".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+
+        assert!(matches!(
+            expired_item.ack(),
+            Err(RedisQueueError::ReservationLost(_))
+        ));
+        reserved_item.nack().unwrap();
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_redeliveries(), 2);
+        reserved_item.ack().unwrap();
+
+        assert_eq!(redis_queue.requeue_expired().unwrap(), 0);
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_background_reaper() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_background_reaper".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List)
+        .with_visibility_timeout(Duration::from_millis(100))
+        .with_inline_reaping(false);
+
+        redis_queue.push(0).unwrap();
+        redis_queue.push(1).unwrap();
+        let _first = redis_queue.reserve().unwrap().unwrap();
+        let _second = redis_queue.reserve().unwrap().unwrap();
+
+        let reaper = redis_queue.start_reaper(Duration::from_millis(50)).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        reaper.stop();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some(0));
+        assert_eq!(redis_queue.pop().unwrap(), Some(1));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_rejects_zero_max_attempts() {
+        let redis_queue: RedisQueue<String> = RedisQueue::new(
+            "test_redis_queue_rejects_zero_max_attempts".to_string(),
+            initialize_redis_client(),
+        );
+        let result = redis_queue.with_max_attempts(0);
+        assert!(matches!(result, Err(RedisQueueError::Configuration(_))));
+    }
+
+    #[test]
+    fn test_redis_queue_dead_letters() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_dead_letters".to_string(),
+            initialize_redis_client(),
+        )
+        .with_max_attempts(2)
+        .unwrap();
+
+        redis_queue.push("poison".to_string()).unwrap();
+        redis_queue.push("test".to_string()).unwrap();
+
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        let element_id = reserved_item.get_id();
+        reserved_item.nack().unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("test".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        let dead_letters = redis_queue.dead_letters().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].get_data(), "poison".to_string());
+        assert_eq!(dead_letters[0].get_attempts(), 2);
+        assert_eq!(dead_letters[0].get_reason(), DeadLetterReason::AttemptsExhausted);
+        let dead_letter = redis_queue.dead_letter(element_id.clone()).unwrap().unwrap();
+        assert_eq!(dead_letter.get_id(), element_id);
+
+        assert!(redis_queue.requeue_dead_letter(element_id.clone()).unwrap());
+        assert!(!redis_queue.requeue_dead_letter(element_id).unwrap());
+        assert!(redis_queue.dead_letters().unwrap().is_empty());
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "poison".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 0);
+        reserved_item.ack().unwrap();
+    }
+
+    #[test]
+    fn test_redis_queue_purge_dead_letters() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_purge_dead_letters".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List)
+        .with_visibility_timeout(Duration::from_millis(50))
+        .with_max_attempts(1)
+        .unwrap();
+
+        redis_queue.push(0).unwrap();
+        redis_queue.push(1).unwrap();
+        let _first = redis_queue.reserve().unwrap().unwrap();
+        let _second = redis_queue.reserve().unwrap().unwrap();
+
+        // Expired elements that used up their attempts are dead-lettered instead of requeued.
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(redis_queue.requeue_expired().unwrap(), 2);
+        assert_eq!(redis_queue.pop().unwrap(), None);
+        assert_eq!(redis_queue.dead_letters().unwrap().len(), 2);
+
+        assert_eq!(redis_queue.purge_dead_letters().unwrap(), 2);
+        assert!(redis_queue.dead_letters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_redis_queue_push_delayed() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_delayed".to_string(),
+            initialize_redis_client(),
+        );
+
+        redis_queue.push_delayed("later".to_string(), Duration::from_millis(200)).unwrap();
+        redis_queue.push("now".to_string()).unwrap();
+        redis_queue
+            .push_at("overdue".to_string(), SystemTime::now() - Duration::from_secs(1))
+            .unwrap();
+
+        // Due elements join the end of the queue.
+        assert_eq!(redis_queue.pop().unwrap(), Some("now".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("overdue".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(redis_queue.pop().unwrap(), Some("later".to_string()));
+
+        // A blocking pop wakes up when a delayed element falls due.
+        redis_queue.push_delayed("waited".to_string(), Duration::from_millis(200)).unwrap();
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_blocking(Duration::from_secs(5)).unwrap();
+        assert_eq!(result, Some("waited".to_string()));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_redis_queue_with_priority_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_with_priority_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Priority);
+
+        redis_queue.push_with_priority("bulk-1".to_string(), 0).unwrap();
+        redis_queue.push_with_priority("urgent-1".to_string(), 10).unwrap();
+        redis_queue.push_with_priority("later".to_string(), -5).unwrap();
+        redis_queue.push("bulk-2".to_string()).unwrap();
+        redis_queue.push_with_priority("urgent-2".to_string(), 10).unwrap();
+
+        // A handed back element stays at the front of its priority level.
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some("urgent-1".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("urgent-2".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("bulk-1".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("bulk-2".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("later".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_push_with_priority_requires_priority_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_with_priority_requires_priority_backend".to_string(),
+            initialize_redis_client(),
+        );
+
+        let result = redis_queue.push_with_priority("test".to_string(), 1);
+        assert!(matches!(result, Err(RedisQueueError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_redis_queue_push_with_ttl() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_with_ttl".to_string(),
+            initialize_redis_client(),
+        );
+
+        let ttl = Duration::from_millis(50);
+        redis_queue.push_with_ttl("stale".to_string(), ttl).unwrap();
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push_with_ttl("stale".to_string(), ttl).unwrap();
+        redis_queue.push_with_ttl("stale".to_string(), ttl).unwrap();
+        redis_queue.push_with_ttl("second".to_string(), Duration::from_secs(60)).unwrap();
+        redis_queue.push_with_ttl("stale".to_string(), ttl).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        // Expired elements are skipped wherever they are in the queue.
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        redis_queue.push("third".to_string()).unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("third".to_string()));
+        assert!(redis_queue.dead_letters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_redis_queue_dead_letter_expired() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_dead_letter_expired".to_string(),
+            initialize_redis_client(),
+        )
+        .with_dead_letter_expired(true);
+        redis_queue.purge_dead_letters().unwrap();
+
+        redis_queue.push_with_ttl("stale".to_string(), Duration::from_millis(50)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(redis_queue.reserve().unwrap().is_none());
+
+        let dead_letters = redis_queue.dead_letters().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].get_data(), "stale".to_string());
+        assert!(dead_letters[0].get_expires_at().unwrap() < SystemTime::now());
+        assert_eq!(dead_letters[0].get_reason(), DeadLetterReason::Expired);
+        assert_eq!(dead_letters[0].get_attempts(), 0);
+        redis_queue.purge_dead_letters().unwrap();
+
+        // An element handed back before it expired was delivered that many times.
+        redis_queue.push_with_ttl("retried".to_string(), Duration::from_millis(200)).unwrap();
+        redis_queue.reserve().unwrap().unwrap().nack().unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(redis_queue.reserve().unwrap().is_none());
+
+        let dead_letters = redis_queue.dead_letters().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].get_reason(), DeadLetterReason::Expired);
+        assert_eq!(dead_letters[0].get_attempts(), 1);
+    }
+
+    #[test]
+    fn test_redis_queue_push_with_dedup_key() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_with_dedup_key".to_string(),
+            initialize_redis_client(),
+        )
+        .with_dedup_window(Duration::from_millis(200));
+
+        let element_id = redis_queue
+            .push_with_dedup_key("first".to_string(), "order-1".to_string())
+            .unwrap();
+        let retried_id = redis_queue
+            .push_with_dedup_key("first".to_string(), "order-1".to_string())
+            .unwrap();
+        assert_eq!(retried_id, element_id);
+        redis_queue
+            .push_with_dedup_key("second".to_string(), "order-2".to_string())
+            .unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        // Once the window has passed the key is accepted again.
+        std::thread::sleep(Duration::from_millis(300));
+        let pushed_again_id = redis_queue
+            .push_with_dedup_key("first".to_string(), "order-1".to_string())
+            .unwrap();
+        assert_ne!(pushed_again_id, element_id);
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+    }
+
+    #[test]
+    fn test_redis_queue_push_with_dedup_key_failure_keeps_key_free() {
+        let mut redis_connection = initialize_redis();
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_with_dedup_key_failure_keeps_key_free".to_string(),
+            initialize_redis_client(),
+        );
+        let queue_key = "redis-queue:test_redis_queue_push_with_dedup_key_failure_keeps_key_free";
+        let _: () = redis_connection
+            .del(&[
+                format!("{}:state:first", queue_key),
+                format!("{}:dedup:order-1", queue_key),
+            ])
+            .unwrap();
+        let _: () = redis_connection
+            .set(format!("{}:state:last", queue_key), "missing-element")
+            .unwrap();
+
+        let result = redis_queue.push_with_dedup_key("test".to_string(), "order-1".to_string());
+        assert!(matches!(result, Err(RedisQueueError::CorruptedState(_))));
+
+        // The failed push did not claim the key, so the retry is pushed.
+        let _: () = redis_connection.del(format!("{}:state:last", queue_key)).unwrap();
+        let element_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .unwrap();
+        let retried_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .unwrap();
+        assert_eq!(retried_id, element_id);
+        assert_eq!(redis_queue.pop().unwrap(), Some("test".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_push_with_dedup_key_on_stream_backend() {
+        let mut redis_connection = initialize_redis();
+        let stream_name =
+            "redis-queue:test_redis_queue_push_with_dedup_key_on_stream_backend:stream";
+        let _: () = redis_connection.del(stream_name).unwrap();
+
+        // A window under a millisecond is rounded up instead of failing every push.
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_with_dedup_key_on_stream_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "consumer".to_string())
+        .with_dedup_window(Duration::ZERO);
+        redis_queue.create_group("group".to_string()).unwrap();
+
+        let element_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("test".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+
+        std::thread::sleep(Duration::from_millis(10));
+        let pushed_again_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .unwrap();
+        assert_ne!(pushed_again_id, element_id);
+        assert_eq!(redis_queue.pop().unwrap(), Some("test".to_string()));
+    }
+
+    #[test]
+    fn test_redis_queue_push_many() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_many".to_string(),
+            initialize_redis_client(),
+        );
+
+        redis_queue.push("before".to_string()).unwrap();
+        let element_ids = redis_queue
+            .push_many(["first", "second", "third"].map(String::from))
+            .unwrap();
+        assert_eq!(element_ids.len(), 3);
+        assert!(redis_queue.push_many(Vec::new()).unwrap().is_empty());
+        redis_queue.push("after".to_string()).unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some("before".to_string()));
+        for (element_id, data) in element_ids.iter().zip(["first", "second", "third"]) {
+            let reserved_item = redis_queue.reserve().unwrap().unwrap();
+            assert_eq!(&reserved_item.get_id(), element_id);
+            assert_eq!(reserved_item.get_data(), data.to_string());
+            reserved_item.ack().unwrap();
+        }
+        assert_eq!(redis_queue.pop().unwrap(), Some("after".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_push_many_with_list_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_push_many_with_list_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List);
+
+        redis_queue.push_many(["first", "second"].map(String::from)).unwrap();
+        redis_queue.push("third".to_string()).unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), Some("third".to_string()));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_pop_many() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_pop_many".to_string(),
+            initialize_redis_client(),
+        );
+
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push_with_ttl("stale".to_string(), Duration::from_millis(50)).unwrap();
+        redis_queue.push_many(["second", "third", "fourth"].map(String::from)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(redis_queue.pop_many(0).unwrap().is_empty());
+        assert_eq!(
+            redis_queue.pop_many(3).unwrap(),
+            ["first", "second", "third"].map(String::from)
+        );
+        assert_eq!(redis_queue.pop_many(3).unwrap(), ["fourth".to_string()]);
+        assert!(redis_queue.pop_many(3).unwrap().is_empty());
+
+        // The queue links up again after being emptied by a batch.
+        redis_queue.push("fifth".to_string()).unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some("fifth".to_string()));
+    }
+
+    #[test]
+    fn test_redis_queue_pop_many_with_priority_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_pop_many_with_priority_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::Priority);
+
+        redis_queue.push("bulk".to_string()).unwrap();
+        redis_queue.push_with_priority("urgent".to_string(), 1).unwrap();
+
+        assert_eq!(redis_queue.pop_many(5).unwrap(), ["urgent", "bulk"].map(String::from));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_peek() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_peek".to_string(),
+            initialize_redis_client(),
+        );
+
+        assert_eq!(redis_queue.peek().unwrap(), None);
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push_with_ttl("stale".to_string(), Duration::from_millis(50)).unwrap();
+        redis_queue.push_many(["second", "third"].map(String::from)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert_eq!(redis_queue.peek().unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.peek_n(2).unwrap(), ["first", "second"].map(String::from));
+        assert_eq!(redis_queue.peek_range(1, 5).unwrap(), ["second", "third"].map(String::from));
+        assert!(redis_queue.peek_range(3, 5).unwrap().is_empty());
+
+        assert_eq!(
+            redis_queue.pop_many(5).unwrap(),
+            ["first", "second", "third"].map(String::from)
+        );
+        assert_eq!(redis_queue.peek().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_peek_includes_due_delayed_elements() {
+        let mut redis_connection = initialize_redis();
+        let _: () = redis_connection
+            .del("redis-queue:test_redis_queue_peek_includes_due_delayed_elements:delayed")
+            .unwrap();
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_peek_includes_due_delayed_elements".to_string(),
+            initialize_redis_client(),
+        );
+
+        redis_queue.push_delayed("due".to_string(), Duration::from_millis(50)).unwrap();
+        redis_queue.push_delayed("later".to_string(), Duration::from_secs(60)).unwrap();
+        assert_eq!(redis_queue.peek().unwrap(), None);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(redis_queue.peek().unwrap(), Some("due".to_string()));
+
+        redis_queue.push("first".to_string()).unwrap();
+        assert_eq!(redis_queue.peek_n(5).unwrap(), ["first", "due"].map(String::from));
+        assert_eq!(redis_queue.peek_range(1, 5).unwrap(), ["due".to_string()]);
+        assert_eq!(redis_queue.pop_many(5).unwrap(), ["first", "due"].map(String::from));
+        assert_eq!(redis_queue.peek().unwrap(), None);
+    }
+
+    #[test]
+    fn test_redis_queue_peek_range_with_list_backend() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_peek_range_with_list_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .with_backend(QueueBackend::List);
+
+        redis_queue.push_many((0..150).map(|i| i.to_string())).unwrap();
+
+        // The range spans two of the pages the list is read in.
+        assert_eq!(
+            redis_queue.peek_range(98, 4).unwrap(),
+            ["98", "99", "100", "101"].map(String::from)
+        );
+        assert_eq!(redis_queue.peek_range(148, 4).unwrap(), ["148", "149"].map(String::from));
+        assert_eq!(redis_queue.pop_many(150).unwrap().len(), 150);
+    }
+
+    #[test]
+    fn test_redis_queue_len() {
+        let mut redis_queue = RedisQueue::new(
+            "test_redis_queue_len".to_string(),
+            initialize_redis_client(),
+        );
+
+        assert_eq!(redis_queue.len().unwrap(), 0);
+        assert!(redis_queue.is_empty().unwrap());
+
+        redis_queue.push("first".to_string()).unwrap();
+        redis_queue.push_many(["second", "third"].map(String::from)).unwrap();
+        redis_queue.push_delayed("delayed".to_string(), Duration::from_millis(50)).unwrap();
+        assert_eq!(redis_queue.len().unwrap(), 3);
+
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        assert_eq!(redis_queue.len().unwrap(), 2);
+        reserved_item.nack().unwrap();
+        assert_eq!(redis_queue.len().unwrap(), 3);
+
+        redis_queue.push_with_ttl("stale".to_string(), Duration::from_millis(50)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(redis_queue.len().unwrap(), 4);
+
+        // The pop promotes the delayed element as it takes the first one.
+        assert_eq!(redis_queue.pop().unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.len().unwrap(), 4);
+
+        assert_eq!(redis_queue.pop_many(10).unwrap().len(), 3);
+        assert_eq!(redis_queue.len().unwrap(), 0);
+        assert!(redis_queue.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_redis_queue_reads_elements_of_the_previous_format() {
+        let mut redis_queue: RedisQueue<Vec<u64>> = RedisQueue::new(
+            "test_redis_queue_reads_elements_of_the_previous_format".to_string(),
+            initialize_redis_client(),
+        );
+        let key = |name: &str| {
+            format!("redis-queue:test_redis_queue_reads_elements_of_the_previous_format:{}", name)
+        };
+
+        // Elements written before `data` was stored as a JSON string, with data that would not
+        // survive being re-encoded by the scripts.
+        let mut redis = initialize_redis();
+        for (name, value) in [
+            ("element:old-1", r#"{"id":"old-1","data":[],"next":"old-2"}"#),
+            ("element:old-2", r#"{"id":"old-2","data":[18446744073709551557],"next":null}"#),
+            ("state:first", "old-1"),
+            ("state:last", "old-2"),
+        ] {
+            let _: () = redis.set(key(name), value).unwrap();
+        }
+
+        assert_eq!(redis_queue.peek_n(2).unwrap(), vec![vec![], vec![18446744073709551557]]);
+        redis_queue.push(vec![1]).unwrap();
+
+        assert_eq!(redis_queue.pop().unwrap(), Some(vec![]));
+        let reserved_item = redis_queue.reserve().unwrap().unwrap();
+        reserved_item.nack().unwrap();
+        assert_eq!(redis_queue.pop().unwrap(), Some(vec![18446744073709551557]));
+        assert_eq!(redis_queue.pop().unwrap(), Some(vec![1]));
+        assert_eq!(redis_queue.pop().unwrap(), None);
+    }
+
     #[test]
     fn test_redis_queue_with_concurrent_push_pop() {
         let redis_queue = RedisQueue::new(
@@ -48,7 +1035,7 @@ mod tests {
             let local_item = item.clone();
 
             let handle = std::thread::spawn(move || {
-                queue.push(local_item.clone());
+                queue.push(local_item.clone()).unwrap();
             });
             handles.push(handle);
         }
@@ -63,7 +1050,7 @@ mod tests {
             let mut queue = redis_queue.clone();
 
             let handle = std::thread::spawn(move || {
-                let value = queue.pop();
+                let value = queue.pop().unwrap();
                 assert_eq!(value.unwrap(), result);
             });
             handles.push(handle);
@@ -80,7 +1067,8 @@ mod tests {
             "initialize_async_redis_queue".to_string(),
             initialize_redis_client(),
         )
-        .await;
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
@@ -89,22 +1077,461 @@ mod tests {
             "test_async_push_pop_to_redis_queue".to_string(),
             initialize_redis_client(),
         )
-        .await;
+        .await
+        .unwrap();
 
         let item = "test".to_string();
 
-        redis_queue.push(item.clone()).await;
-        let result = redis_queue.pop().await.unwrap();
+        redis_queue.push(item.clone()).await.unwrap();
+        let result = redis_queue.pop().await.unwrap().unwrap();
         assert_eq!(result, item);
     }
 
+    #[tokio::test]
+    async fn test_async_redis_queue_keeps_order() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_keeps_order".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+
+        for item in 0..5 {
+            redis_queue.push(item).await.unwrap();
+        }
+        for item in 0..5 {
+            assert_eq!(redis_queue.pop().await.unwrap(), Some(item));
+        }
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_with_list_backend() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_with_list_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List);
+
+        for item in 0..5 {
+            redis_queue.push(item).await.unwrap();
+        }
+        for item in 0..5 {
+            assert_eq!(redis_queue.pop().await.unwrap(), Some(item));
+        }
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_with_stream_backend() {
+        let mut redis_connection = initialize_async_redis().await;
+        let stream_name = "redis-queue:test_async_redis_queue_with_stream_backend:stream";
+        let _: () = redis_connection.del(stream_name).await.unwrap();
+
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_with_stream_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::Stream)
+        .with_consumer_group("group".to_string(), "consumer".to_string());
+        redis_queue.create_group("group".to_string()).await.unwrap();
+
+        for item in 0..3 {
+            redis_queue.push(item).await.unwrap();
+        }
+        assert_eq!(redis_queue.len().await.unwrap(), 3);
+        assert_eq!(redis_queue.pop().await.unwrap(), Some(0));
+
+        // An entry read by a consumer that died before acknowledging it.
+        let read_options = StreamReadOptions::default()
+            .group("group", "crashed-consumer")
+            .count(1);
+        let _: StreamReadReply = redis_connection
+            .xread_options(&[stream_name], &[">"], &read_options)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let claimed = redis_queue
+            .claim_stale(Duration::from_millis(100), 10)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].get_data(), 1);
+        assert_eq!(claimed[0].get_redeliveries(), 1);
+        for reserved_item in claimed {
+            reserved_item.ack().await.unwrap();
+        }
+
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), 2);
+        reserved_item.nack().await.unwrap();
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), 2);
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+        reserved_item.ack().await.unwrap();
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+        let claimed = redis_queue
+            .claim_stale(Duration::from_millis(0), 10)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        let result = redis_queue.start_reaper(Duration::from_millis(50));
+        assert!(matches!(result, Err(RedisQueueError::Unsupported(_))));
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_pop_wait_times_out() {
+        let mut redis_queue: AsyncRedisQueue<String> = AsyncRedisQueue::new(
+            "test_async_redis_queue_pop_wait_times_out".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_wait(Duration::from_millis(300)).await.unwrap();
+        assert_eq!(result, None);
+        assert!(started_at.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_pop_wait_woken_by_push() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_pop_wait_woken_by_push".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List);
+
+        let mut producer = redis_queue.clone();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            producer.push("test".to_string()).await.unwrap();
+        });
+
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_wait(Duration::from_secs(10)).await.unwrap();
+        assert_eq!(result, Some("test".to_string()));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_reserve_ack_and_nack() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_reserve_ack_and_nack".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+
+        redis_queue.push("first".to_string()).await.unwrap();
+        redis_queue.push("second".to_string()).await.unwrap();
+
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "first".to_string());
+        reserved_item.nack().await.unwrap();
+
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "first".to_string());
+        reserved_item.ack().await.unwrap();
+
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_visibility_timeout() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_visibility_timeout".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_visibility_timeout(Duration::from_millis(100));
+
+        redis_queue.push("first".to_string()).await.unwrap();
+        redis_queue.push("second".to_string()).await.unwrap();
+        let expired_item = redis_queue.reserve().await.unwrap().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("first".to_string()));
+        assert!(matches!(
+            expired_item.ack().await,
+            Err(RedisQueueError::ReservationLost(_))
+        ));
+
+        let mut redis_queue = redis_queue.with_inline_reaping(false);
+        let _reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        let reaper = redis_queue.start_reaper(Duration::from_millis(50)).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        reaper.stop().await;
+
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        assert_eq!(reserved_item.get_data(), "second".to_string());
+        assert_eq!(reserved_item.get_redeliveries(), 1);
+        reserved_item.ack().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_rejects_zero_max_attempts() {
+        let redis_queue: AsyncRedisQueue<String> = AsyncRedisQueue::new(
+            "test_async_redis_queue_rejects_zero_max_attempts".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+        let result = redis_queue.with_max_attempts(0);
+        assert!(matches!(result, Err(RedisQueueError::Configuration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_dead_letters() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_dead_letters".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_max_attempts(1)
+        .unwrap();
+
+        redis_queue.push("poison".to_string()).await.unwrap();
+        let reserved_item = redis_queue.reserve().await.unwrap().unwrap();
+        let element_id = reserved_item.get_id();
+        reserved_item.nack().await.unwrap();
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+
+        let dead_letter = redis_queue.dead_letter(element_id.clone()).await.unwrap().unwrap();
+        assert_eq!(dead_letter.get_data(), "poison".to_string());
+        assert_eq!(dead_letter.get_attempts(), 1);
+        assert_eq!(redis_queue.dead_letters().await.unwrap().len(), 1);
+
+        assert!(redis_queue.requeue_dead_letter(element_id).await.unwrap());
+        assert_eq!(redis_queue.purge_dead_letters().await.unwrap(), 0);
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("poison".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_push_delayed() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_push_delayed".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List);
+
+        redis_queue
+            .push_delayed("later".to_string(), Duration::from_millis(200))
+            .await
+            .unwrap();
+        redis_queue.push("now".to_string()).await.unwrap();
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("now".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+
+        let started_at = std::time::Instant::now();
+        let result = redis_queue.pop_wait(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(result, Some("later".to_string()));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_with_priority_backend() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_with_priority_backend".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::Priority);
+
+        redis_queue.push("bulk".to_string()).await.unwrap();
+        redis_queue
+            .push_with_priority("urgent".to_string(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("urgent".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("bulk".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_push_with_ttl() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_push_with_ttl".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List);
+
+        redis_queue
+            .push_with_ttl("stale".to_string(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        redis_queue.push("fresh".to_string()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("fresh".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_push_with_dedup_key() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_push_with_dedup_key".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List)
+        .with_dedup_window(Duration::from_millis(200));
+
+        let element_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .await
+            .unwrap();
+        let retried_id = redis_queue
+            .push_with_dedup_key("test".to_string(), "order-1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(retried_id, element_id);
+
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("test".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_push_many() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_push_many".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+
+        redis_queue.push("before".to_string()).await.unwrap();
+        let element_ids = redis_queue
+            .push_many(["first", "second"].map(String::from))
+            .await
+            .unwrap();
+        assert_eq!(element_ids.len(), 2);
+        redis_queue.push("after".to_string()).await.unwrap();
+
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("before".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("first".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("second".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), Some("after".to_string()));
+        assert_eq!(redis_queue.pop().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_pop_many() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_pop_many".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap();
+
+        redis_queue
+            .push_many(["first", "second", "third"].map(String::from))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            redis_queue.pop_many(2).await.unwrap(),
+            ["first", "second"].map(String::from)
+        );
+        assert_eq!(redis_queue.pop_many(2).await.unwrap(), ["third".to_string()]);
+        assert!(redis_queue.pop_many(2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_peek() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_peek".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::Priority);
+
+        redis_queue.push("bulk".to_string()).await.unwrap();
+        redis_queue
+            .push_with_priority("urgent".to_string(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(redis_queue.peek().await.unwrap(), Some("urgent".to_string()));
+        assert_eq!(
+            redis_queue.peek_n(5).await.unwrap(),
+            ["urgent", "bulk"].map(String::from)
+        );
+        assert_eq!(redis_queue.peek_range(1, 5).await.unwrap(), ["bulk".to_string()]);
+
+        // A due delayed element is read at the end of its own priority level.
+        redis_queue
+            .push_with_priority("lowest".to_string(), -1)
+            .await
+            .unwrap();
+        redis_queue
+            .push_delayed("retry".to_string(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            redis_queue.peek_n(5).await.unwrap(),
+            ["urgent", "bulk", "retry", "lowest"].map(String::from)
+        );
+        assert_eq!(
+            redis_queue.pop_many(5).await.unwrap(),
+            ["urgent", "bulk", "retry", "lowest"].map(String::from)
+        );
+        assert_eq!(redis_queue.peek().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_redis_queue_len() {
+        let mut redis_queue = AsyncRedisQueue::new(
+            "test_async_redis_queue_len".to_string(),
+            initialize_redis_client(),
+        )
+        .await
+        .unwrap()
+        .with_backend(QueueBackend::List);
+
+        redis_queue
+            .push_many(["first", "second"].map(String::from))
+            .await
+            .unwrap();
+        redis_queue.push("third".to_string()).await.unwrap();
+        assert_eq!(redis_queue.len().await.unwrap(), 3);
+
+        redis_queue.pop().await.unwrap();
+        assert_eq!(redis_queue.len().await.unwrap(), 2);
+
+        redis_queue.pop_many(5).await.unwrap();
+        assert!(redis_queue.is_empty().await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_async_redis_queue_with_concurrent_push_pop() {
         let redis_queue = AsyncRedisQueue::new(
             "test_async_redis_queue_with_concurrent_push_pop".to_string(),
             initialize_redis_client(),
         )
-        .await;
+        .await
+        .unwrap();
         let item = "test".to_string();
         
         let num_tasks = 100;
@@ -115,7 +1542,7 @@ mod tests {
             let local_item = item.clone();
 
             let handle = tokio::spawn(async move {
-                queue.push(local_item.clone()).await;
+                queue.push(local_item.clone()).await.unwrap();
             });
             handles.push(handle);
         }
@@ -130,7 +1557,7 @@ mod tests {
             let mut queue = redis_queue.clone();
 
             let handle = tokio::spawn(async move {
-                let value = queue.pop().await;
+                let value = queue.pop().await.unwrap();
                 assert_eq!(value.unwrap(), result);
             });
             handles.push(handle);
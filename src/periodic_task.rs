@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Paces the loop of a [`PeriodicTask`].
+pub struct Ticker {
+    interval: Duration,
+    stop_receiver: mpsc::Receiver<()>,
+}
+
+impl Ticker {
+    /// Waits for the next tick, `false` once the task is stopped.
+    pub fn tick(&self) -> bool {
+        matches!(
+            self.stop_receiver.recv_timeout(self.interval),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        )
+    }
+}
+
+/// Background thread running a loop paced by a [`Ticker`], until the loop ends or the task is
+/// stopped or dropped.
+pub struct PeriodicTask {
+    stop_sender: Option<mpsc::Sender<()>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl PeriodicTask {
+    pub fn start<F>(interval: Duration, run: F) -> Self
+    where F: FnOnce(Ticker) + Send + 'static {
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+        let ticker = Ticker {
+            interval,
+            stop_receiver,
+        };
+
+        PeriodicTask {
+            stop_sender: Some(stop_sender),
+            handle: Some(std::thread::spawn(move || run(ticker))),
+        }
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        drop(self.stop_sender.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for PeriodicTask {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Paces the loop of an [`AsyncPeriodicTask`].
+pub struct AsyncTicker {
+    interval: Duration,
+    stop_receiver: async_std::channel::Receiver<()>,
+}
+
+impl AsyncTicker {
+    /// Waits for the next tick, `false` once the task is stopped.
+    pub async fn tick(&self) -> bool {
+        async_std::future::timeout(self.interval, self.stop_receiver.recv())
+            .await
+            .is_err()
+    }
+}
+
+/// Background task running a loop paced by an [`AsyncTicker`], until the loop ends or the task
+/// is stopped or dropped.
+pub struct AsyncPeriodicTask {
+    stop_sender: Option<async_std::channel::Sender<()>>,
+    handle: Option<async_std::task::JoinHandle<()>>,
+}
+
+impl AsyncPeriodicTask {
+    pub fn start<F, R>(interval: Duration, run: F) -> Self
+    where
+        F: FnOnce(AsyncTicker) -> R,
+        R: Future<Output = ()> + Send + 'static,
+    {
+        let (stop_sender, stop_receiver) = async_std::channel::bounded::<()>(1);
+        let ticker = AsyncTicker {
+            interval,
+            stop_receiver,
+        };
+
+        AsyncPeriodicTask {
+            stop_sender: Some(stop_sender),
+            handle: Some(async_std::task::spawn(run(ticker))),
+        }
+    }
+
+    pub async fn stop(mut self) {
+        drop(self.stop_sender.take());
+        if let Some(handle) = self.handle.take() {
+            handle.await;
+        }
+    }
+}
+
+impl Drop for AsyncPeriodicTask {
+    fn drop(&mut self) {
+        // Closing the channel is enough for the task to exit on its own.
+        drop(self.stop_sender.take());
+    }
+}
@@ -0,0 +1,79 @@
+use redis::streams::StreamId;
+
+use crate::error::RedisQueueError;
+
+/// How a queue stores its elements in Redis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueBackend {
+    /// One key per element, linked through their `next` ids from `state:first` to `state:last`.
+    #[default]
+    LinkedList,
+    /// A single Redis list, pushed with `LPUSH` and popped with `RPOP`.
+    List,
+    /// A Redis stream read through consumer groups, so each group gets every element and
+    /// tracks its own pending entries.
+    Stream,
+    /// One Redis list per priority level, with the levels kept in a sorted set. `pop` takes from
+    /// the highest level first, in push order within a level.
+    Priority,
+}
+
+impl QueueBackend {
+    /// Fails with `Unsupported` for operations the stream backend has no equivalent for.
+    pub(crate) fn reject_stream(&self, operation: &str) -> Result<(), RedisQueueError> {
+        match self {
+            QueueBackend::Stream => Err(RedisQueueError::Unsupported(format!(
+                "{} is not available on the stream backend",
+                operation
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn require_priority(&self, operation: &str) -> Result<(), RedisQueueError> {
+        match self {
+            QueueBackend::Priority => Ok(()),
+            _ => Err(RedisQueueError::Unsupported(format!(
+                "{} requires the priority backend",
+                operation
+            ))),
+        }
+    }
+
+    pub(crate) fn require_stream(&self, operation: &str) -> Result<(), RedisQueueError> {
+        match self {
+            QueueBackend::Stream => Ok(()),
+            _ => Err(RedisQueueError::Unsupported(format!(
+                "{} requires the stream backend",
+                operation
+            ))),
+        }
+    }
+}
+
+/// Lists the entries of a consumer group pending for at least `min_idle` milliseconds, oldest
+/// first, with how often each was delivered.
+pub(crate) fn stale_entries_command(
+    stream: String,
+    group: String,
+    min_idle: usize,
+    count: usize,
+) -> redis::Cmd {
+    let mut command = redis::cmd("XPENDING");
+    command
+        .arg(stream)
+        .arg(group)
+        .arg("IDLE")
+        .arg(min_idle)
+        .arg("-")
+        .arg("+")
+        .arg(count);
+    command
+}
+
+/// The element stored in a stream entry.
+pub(crate) fn stream_entry_data(entry: &StreamId) -> Result<String, RedisQueueError> {
+    entry.get::<String>("element").ok_or_else(|| {
+        RedisQueueError::CorruptedState(format!("stream entry {} has no element", entry.id))
+    })
+}
@@ -1,65 +1,195 @@
-use redis::aio::MultiplexedConnection;
-use redis::{AsyncCommands, ExistenceCheck, SetOptions};
+use async_std::stream::StreamExt;
+use std::time::Instant;
 use uuid::Uuid;
 
+use crate::error::RedisQueueError;
+use crate::queue_lock::async_queue_lock_guard::AsyncQueueLockGuard;
+use crate::queue_lock::lock_backoff::LockBackoff;
+use crate::queue_lock::lock_lease::LockLease;
+use crate::queue_lock::lock_nodes::AsyncLockNodes;
+use crate::queue_lock::lock_scripts::{
+    acquire_lock_script, raise_fence_script, release_lock_script,
+};
+use crate::queue_lock::lock_watchdog::AsyncLockWatchdog;
+
+/// A lock on one Redis node, or on a majority of several independent nodes (Redlock) when it
+/// is built with more than one client.
 #[derive(Clone)]
 pub struct AsyncQueueLock {
-    redis_connection: MultiplexedConnection,
-    retry_interval: u64,
+    lock_nodes: AsyncLockNodes,
+    backoff: LockBackoff,
+    lease: LockLease,
 
     queue_name: String,
 }
 
 impl AsyncQueueLock {
-    pub fn new(
+    pub(crate) fn new(
         queue_name: String,
-        redis_connection: MultiplexedConnection,
-        retry_interval: Option<u64>,
+        redis_clients: Vec<redis::Client>,
+        backoff: LockBackoff,
+        lease: LockLease,
     ) -> Self {
         AsyncQueueLock {
-            redis_connection,
-            retry_interval: retry_interval.unwrap_or(100),
-            queue_name: queue_name.to_string(),
+            lock_nodes: AsyncLockNodes::new(redis_clients),
+            backoff,
+            lease,
+            queue_name,
         }
     }
 
-    pub async fn lock<F, R>(&mut self, f: F) -> <R as std::future::Future>::Output
+    pub async fn lock<F, R>(
+        &mut self,
+        f: F,
+    ) -> Result<<R as std::future::Future>::Output, RedisQueueError>
     where
         F: FnOnce() -> R,
         R: std::future::Future,
     {
-        let lock_identifier = Uuid::new_v4().to_string();
-        while !self.try_lock(lock_identifier.clone()).await {
-            async_std::task::sleep(std::time::Duration::from_millis(self.retry_interval)).await;
-        }
+        // The guard also releases the lock if `f` panics or this future is dropped.
+        let guard = self.acquire().await?;
 
         let result = f().await;
 
-        self.unlock().await;
+        guard.release().await?;
+
+        Ok(result)
+    }
+
+    pub async fn acquire(&mut self) -> Result<AsyncQueueLockGuard<'_>, RedisQueueError> {
+        let lock_identifier = Uuid::new_v4().to_string();
+        let fencing_token = self.wait_for_lock(lock_identifier.clone()).await?;
+        let watchdog = self.start_watchdog(lock_identifier.clone());
 
-        result
+        Ok(AsyncQueueLockGuard::new(
+            self,
+            lock_identifier,
+            fencing_token,
+            watchdog,
+        ))
     }
 
     pub fn get_lock_name(&self) -> String {
         format!("redis-queue:{}:lock", self.queue_name)
     }
 
-    async fn try_lock(&mut self, lock_identifier: String) -> bool {
-        let set_options = SetOptions::default()
-            .conditional_set(ExistenceCheck::NX)
-            .get(true);
-        
-        let active_lock_identifier = self.redis_connection
-            .set_options::<String, String, String>(self.get_lock_name(), lock_identifier.clone(), set_options)
-            .await.unwrap_or("".to_string());
-        
-        return active_lock_identifier == lock_identifier;
+    pub fn get_release_channel_name(&self) -> String {
+        format!("redis-queue:{}:lock:released", self.queue_name)
+    }
+
+    pub fn get_fence_name(&self) -> String {
+        format!("redis-queue:{}:lock:fence", self.queue_name)
+    }
+
+    /// Releases the lock on every node, `false` when it was no longer held on a majority.
+    pub(crate) async fn unlock(&mut self, lock_identifier: String) -> Result<bool, RedisQueueError> {
+        let mut release_lock = release_lock_script().key(self.get_lock_name());
+        release_lock
+            .arg(lock_identifier)
+            .arg(self.get_release_channel_name());
+        let released = self.lock_nodes.invoke::<u8>(&release_lock).await?;
+
+        Ok(released.iter().filter(|reply| **reply == Some(1)).count() >= self.lock_nodes.quorum())
+    }
+
+    /// Releases the lock from a background task, for callers that cannot await.
+    pub(crate) fn spawn_unlock(&self, lock_identifier: String) {
+        let mut lock_nodes = self.lock_nodes.clone();
+        let mut release_lock = release_lock_script().key(self.get_lock_name());
+        release_lock
+            .arg(lock_identifier)
+            .arg(self.get_release_channel_name());
+
+        async_std::task::spawn(async move {
+            let _ = lock_nodes.invoke::<u8>(&release_lock).await;
+        });
+    }
+
+    async fn wait_for_lock(&mut self, lock_identifier: String) -> Result<u64, RedisQueueError> {
+        let started_at = Instant::now();
+        if let Some(fencing_token) = self.try_lock(lock_identifier.clone()).await? {
+            return Ok(fencing_token);
+        }
+
+        // Subscribe before retrying so a release between the two attempts is not missed. With
+        // several nodes only the first one reachable is listened to, the others are covered by
+        // polling, like every node is when none can be subscribed to.
+        let mut release_messages = self
+            .lock_nodes
+            .subscribe(self.get_release_channel_name())
+            .await;
+
+        let mut attempt = 0;
+        loop {
+            if let Some(fencing_token) = self.try_lock(lock_identifier.clone()).await? {
+                return Ok(fencing_token);
+            }
+
+            let delay = self
+                .backoff
+                .next_delay(attempt, started_at)
+                .ok_or_else(|| RedisQueueError::LockTimeout(self.get_lock_name()))?;
+
+            // The backoff delay only bounds the wait, so a missed notification degrades to polling.
+            match release_messages.as_mut() {
+                Some(messages) => {
+                    if let Ok(None) = async_std::future::timeout(delay, messages.next()).await {
+                        release_messages = None;
+                        async_std::task::sleep(delay).await;
+                    }
+                }
+                None => async_std::task::sleep(delay).await,
+            }
+            attempt += 1;
+        }
+    }
+
+    fn start_watchdog(&self, lock_identifier: String) -> Option<AsyncLockWatchdog> {
+        self.lease.renew_interval().map(|renew_interval| {
+            AsyncLockWatchdog::start(
+                self.lock_nodes.clone(),
+                self.get_lock_name(),
+                lock_identifier,
+                self.lease.lease_time(),
+                renew_interval,
+            )
+        })
     }
 
-    async fn unlock(&mut self) {
-        self.redis_connection
-            .del::<String, u8>(self.get_lock_name())
-            .await
-            .unwrap();
+    async fn try_lock(&mut self, lock_identifier: String) -> Result<Option<u64>, RedisQueueError> {
+        let started_at = Instant::now();
+        let mut acquire_lock = acquire_lock_script().key(self.get_lock_name());
+        acquire_lock
+            .key(self.get_fence_name())
+            .arg(lock_identifier.clone())
+            .arg(self.lease.lease_time());
+        let fencing_tokens = self.lock_nodes.invoke::<u64>(&acquire_lock).await?;
+
+        let acquired = fencing_tokens
+            .iter()
+            .filter(|fencing_token| fencing_token.is_some_and(|token| token > 0))
+            .count();
+        if acquired == 0 {
+            return Ok(None);
+        }
+
+        // With several nodes, a lock held on too few of them, or that took too long to take, is
+        // given back. A single node holds the lock for its whole lease.
+        if self.lock_nodes.node_count() > 1
+            && (acquired < self.lock_nodes.quorum()
+                || self.lease.validity_time(started_at.elapsed()).is_none())
+        {
+            self.unlock(lock_identifier).await?;
+            return Ok(None);
+        }
+
+        let fencing_token = fencing_tokens.iter().flatten().copied().max().unwrap_or_default();
+        if self.lock_nodes.node_count() > 1 {
+            let mut raise_fence = raise_fence_script().key(self.get_fence_name());
+            raise_fence.arg(fencing_token);
+            self.lock_nodes.invoke::<u8>(&raise_fence).await?;
+        }
+
+        Ok(Some(fencing_token))
     }
 }
@@ -0,0 +1,66 @@
+use crate::error::RedisQueueError;
+use crate::queue_lock::async_queue_lock::AsyncQueueLock;
+use crate::queue_lock::lock_watchdog::AsyncLockWatchdog;
+
+/// Holds an [`AsyncQueueLock`] until [`release`](Self::release) is awaited. A guard dropped
+/// without being released, by a panic or a cancelled future, releases it from a background task.
+pub struct AsyncQueueLockGuard<'a> {
+    queue_lock: &'a mut AsyncQueueLock,
+    lock_identifier: String,
+    fencing_token: u64,
+    watchdog: Option<AsyncLockWatchdog>,
+    released: bool,
+}
+
+impl<'a> AsyncQueueLockGuard<'a> {
+    pub(crate) fn new(
+        queue_lock: &'a mut AsyncQueueLock,
+        lock_identifier: String,
+        fencing_token: u64,
+        watchdog: Option<AsyncLockWatchdog>,
+    ) -> Self {
+        AsyncQueueLockGuard {
+            queue_lock,
+            lock_identifier,
+            fencing_token,
+            watchdog,
+            released: false,
+        }
+    }
+
+    pub fn get_lock_name(&self) -> String {
+        self.queue_lock.get_lock_name()
+    }
+
+    /// Token issued for this acquisition, greater than the token of any earlier holder.
+    pub fn fencing_token(&self) -> u64 {
+        self.fencing_token
+    }
+
+    /// Releases the lock, reporting `LockLost` if its lease ran out and another client took it.
+    pub async fn release(mut self) -> Result<(), RedisQueueError> {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.stop().await;
+        }
+
+        let released = self.queue_lock.unlock(self.lock_identifier.clone()).await?;
+        self.released = true;
+
+        if !released {
+            return Err(RedisQueueError::LockLost(self.get_lock_name()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AsyncQueueLockGuard<'_> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
+        drop(self.watchdog.take());
+        self.queue_lock.spawn_unlock(self.lock_identifier.clone());
+    }
+}
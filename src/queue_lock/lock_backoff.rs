@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct LockBackoff {
+    retry_interval: u64,
+    max_retry_interval: u64,
+    jitter: bool,
+    max_wait: Option<u64>,
+}
+
+impl Default for LockBackoff {
+    fn default() -> Self {
+        LockBackoff {
+            retry_interval: 100,
+            max_retry_interval: 1000,
+            jitter: true,
+            max_wait: None,
+        }
+    }
+}
+
+impl LockBackoff {
+    pub fn with_retry_interval(mut self, retry_interval: u64) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn with_max_retry_interval(mut self, max_retry_interval: u64) -> Self {
+        self.max_retry_interval = max_retry_interval;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_max_wait(mut self, max_wait: u64) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Time to sleep before the next attempt, or `None` once `max_wait` has passed.
+    pub fn next_delay(&self, attempt: u32, started_at: Instant) -> Option<Duration> {
+        let delay = self.delay(attempt);
+
+        match self.max_wait {
+            None => Some(delay),
+            Some(max_wait) => {
+                let remaining = Duration::from_millis(max_wait).checked_sub(started_at.elapsed())?;
+                if remaining.is_zero() {
+                    return None;
+                }
+                Some(delay.min(remaining))
+            }
+        }
+    }
+
+    /// Exponential backoff capped at `max_retry_interval`, jittered within its upper half.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let multiplier = 1u64.checked_shl(attempt).unwrap_or(u64::MAX);
+        let backoff = self
+            .retry_interval
+            .saturating_mul(multiplier)
+            .min(self.max_retry_interval.max(self.retry_interval));
+
+        if !self.jitter || backoff == 0 {
+            return Duration::from_millis(backoff);
+        }
+
+        let half = backoff / 2;
+        let random = (Uuid::new_v4().as_u128() as u64) % (backoff - half + 1);
+        Duration::from_millis(half + random)
+    }
+}
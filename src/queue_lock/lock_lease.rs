@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct LockLease {
+    lease_time: u64,
+    watchdog: bool,
+    clock_drift_factor: f64,
+}
+
+impl Default for LockLease {
+    fn default() -> Self {
+        LockLease {
+            lease_time: 30000,
+            watchdog: false,
+            clock_drift_factor: 0.01,
+        }
+    }
+}
+
+impl LockLease {
+    pub fn with_lease_time(mut self, lease_time: u64) -> Self {
+        self.lease_time = lease_time;
+        self
+    }
+
+    pub fn with_watchdog(mut self, watchdog: bool) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    pub fn with_clock_drift_factor(mut self, clock_drift_factor: f64) -> Self {
+        self.clock_drift_factor = clock_drift_factor;
+        self
+    }
+
+    pub fn lease_time(&self) -> u64 {
+        self.lease_time
+    }
+
+    /// How often the watchdog extends the lease, a third of it so two renewals can fail
+    /// before the lock expires. `None` when the watchdog is disabled.
+    pub fn renew_interval(&self) -> Option<Duration> {
+        if !self.watchdog {
+            return None;
+        }
+        Some(Duration::from_millis((self.lease_time / 3).max(1)))
+    }
+
+    /// How long a lock that took `elapsed` to acquire can still be relied on, after allowing for
+    /// clock drift between the nodes. `None` when the lease is already used up.
+    pub fn validity_time(&self, elapsed: Duration) -> Option<Duration> {
+        let clock_drift = (self.lease_time as f64 * self.clock_drift_factor) as u64 + 2;
+        Duration::from_millis(self.lease_time)
+            .checked_sub(elapsed + Duration::from_millis(clock_drift))
+            .filter(|validity_time| !validity_time.is_zero())
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use async_std::stream::Stream;
+use redis::aio::MultiplexedConnection;
+use redis::{Connection, FromRedisValue, RedisError, ScriptInvocation};
+
+/// How long a node has to accept a connection before it counts as failed, in quorum mode. A
+/// single node is given as long as it takes, like a plain connection.
+const NODE_CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Number of nodes a lock has to be held on, a majority of them.
+pub fn quorum(nodes: usize) -> usize {
+    nodes / 2 + 1
+}
+
+fn connect_timeout(nodes: usize) -> Option<Duration> {
+    (nodes > 1).then_some(NODE_CONNECT_TIMEOUT)
+}
+
+/// The nodes a lock is held on. Each node is connected to when it is first used, and again
+/// after it failed, so a node that is down or restarting only counts against the quorum.
+pub struct LockNodes {
+    redis_clients: Vec<redis::Client>,
+    redis_connections: Vec<Option<Connection>>,
+    connect_timeout: Option<Duration>,
+}
+
+impl LockNodes {
+    pub fn new(redis_clients: Vec<redis::Client>) -> Self {
+        LockNodes {
+            redis_connections: redis_clients.iter().map(|_| None).collect(),
+            connect_timeout: connect_timeout(redis_clients.len()),
+            redis_clients,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.redis_clients.len()
+    }
+
+    pub fn quorum(&self) -> usize {
+        quorum(self.node_count())
+    }
+
+    /// Runs a lock script on every node. Nodes that fail only count against the quorum, the
+    /// error is returned when none of them replied.
+    pub fn invoke<R: FromRedisValue>(
+        &mut self,
+        invocation: &ScriptInvocation<'_>,
+    ) -> Result<Vec<Option<R>>, RedisError> {
+        let mut replies = Vec::with_capacity(self.node_count());
+        let mut last_error = None;
+        for (redis_client, redis_connection) in
+            self.redis_clients.iter().zip(self.redis_connections.iter_mut())
+        {
+            let reply = match (redis_connection.take(), self.connect_timeout) {
+                (Some(connection), _) => Ok(connection),
+                (None, Some(timeout)) => redis_client.get_connection_with_timeout(timeout),
+                (None, None) => redis_client.get_connection(),
+            }
+            .and_then(|connection| invocation.invoke(redis_connection.insert(connection)));
+
+            match reply {
+                Ok(reply) => replies.push(Some(reply)),
+                Err(err) => {
+                    *redis_connection = None;
+                    last_error = Some(err);
+                    replies.push(None);
+                }
+            }
+        }
+
+        into_replies(replies, last_error)
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncLockNodes {
+    redis_clients: Vec<redis::Client>,
+    redis_connections: Vec<Option<MultiplexedConnection>>,
+    connect_timeout: Option<Duration>,
+}
+
+impl AsyncLockNodes {
+    pub fn new(redis_clients: Vec<redis::Client>) -> Self {
+        AsyncLockNodes {
+            redis_connections: redis_clients.iter().map(|_| None).collect(),
+            connect_timeout: connect_timeout(redis_clients.len()),
+            redis_clients,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.redis_clients.len()
+    }
+
+    pub fn quorum(&self) -> usize {
+        quorum(self.node_count())
+    }
+
+    pub async fn invoke<R: FromRedisValue>(
+        &mut self,
+        invocation: &ScriptInvocation<'_>,
+    ) -> Result<Vec<Option<R>>, RedisError> {
+        let mut replies = Vec::with_capacity(self.node_count());
+        let mut last_error = None;
+        for (redis_client, redis_connection) in
+            self.redis_clients.iter().zip(self.redis_connections.iter_mut())
+        {
+            let connection = match (redis_connection.take(), self.connect_timeout) {
+                (Some(connection), _) => Ok(connection),
+                (None, Some(timeout)) => {
+                    redis_client
+                        .get_multiplexed_async_connection_with_timeouts(Duration::MAX, timeout)
+                        .await
+                }
+                (None, None) => redis_client.get_multiplexed_async_connection().await,
+            };
+            let reply = match connection {
+                Ok(connection) => {
+                    invocation.invoke_async(redis_connection.insert(connection)).await
+                }
+                Err(err) => Err(err),
+            };
+
+            match reply {
+                Ok(reply) => replies.push(Some(reply)),
+                Err(err) => {
+                    *redis_connection = None;
+                    last_error = Some(err);
+                    replies.push(None);
+                }
+            }
+        }
+
+        into_replies(replies, last_error)
+    }
+
+    /// Subscribes to a channel on the first node that can be reached, `None` when none can.
+    pub async fn subscribe(&self, channel: String) -> Option<impl Stream<Item = redis::Msg>> {
+        for redis_client in &self.redis_clients {
+            let subscribe = async {
+                let mut pubsub = redis_client.get_async_pubsub().await?;
+                pubsub.subscribe(&channel).await?;
+                Ok::<_, RedisError>(pubsub)
+            };
+            let subscribed = match self.connect_timeout {
+                Some(timeout) => async_std::future::timeout(timeout, subscribe).await.ok(),
+                None => Some(subscribe.await),
+            };
+
+            if let Some(Ok(pubsub)) = subscribed {
+                return Some(pubsub.into_on_message());
+            }
+        }
+        None
+    }
+}
+
+fn into_replies<R>(
+    replies: Vec<Option<R>>,
+    last_error: Option<RedisError>,
+) -> Result<Vec<Option<R>>, RedisError> {
+    match last_error {
+        Some(err) if replies.iter().all(Option::is_none) => Err(err),
+        _ => Ok(replies),
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::OnceLock;
+
+use redis::Script;
+
+// Takes the lock and issues the next fencing token in one step, 0 means the lock is held.
+const ACQUIRE_LOCK_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return redis.call('INCR', KEYS[2])
+end
+return 0
+"#;
+
+pub fn acquire_lock_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(ACQUIRE_LOCK_SCRIPT))
+}
+
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    redis.call('DEL', KEYS[1])
+    redis.call('PUBLISH', ARGV[2], ARGV[1])
+    return 1
+end
+return 0
+"#;
+
+pub fn release_lock_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(RELEASE_LOCK_SCRIPT))
+}
+
+const EXTEND_LOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+pub fn extend_lock_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(EXTEND_LOCK_SCRIPT))
+}
+
+// Moves the fencing counter up to ARGV[1], so a later majority sees at least that token.
+const RAISE_FENCE_SCRIPT: &str = r#"
+if (tonumber(redis.call('GET', KEYS[1])) or 0) < tonumber(ARGV[1]) then
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+return 1
+"#;
+
+pub fn raise_fence_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(RAISE_FENCE_SCRIPT))
+}
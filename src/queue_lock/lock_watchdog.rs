@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use crate::periodic_task::{AsyncPeriodicTask, PeriodicTask};
+use crate::queue_lock::lock_nodes::{AsyncLockNodes, LockNodes};
+use crate::queue_lock::lock_scripts::extend_lock_script;
+
+pub struct LockWatchdog {
+    task: PeriodicTask,
+}
+
+impl LockWatchdog {
+    pub fn start(
+        mut lock_nodes: LockNodes,
+        lock_name: String,
+        lock_identifier: String,
+        lease_time: u64,
+        renew_interval: Duration,
+    ) -> Self {
+        let task = PeriodicTask::start(renew_interval, move |ticker| {
+            while ticker.tick() {
+                let mut extend_lock = extend_lock_script().key(&lock_name);
+                extend_lock.arg(&lock_identifier).arg(lease_time);
+                let extended = lock_nodes.invoke::<u8>(&extend_lock);
+
+                if !is_extended(extended, lock_nodes.quorum()) {
+                    break;
+                }
+            }
+        });
+
+        LockWatchdog { task }
+    }
+
+    pub fn stop(self) {
+        self.task.stop();
+    }
+}
+
+pub struct AsyncLockWatchdog {
+    task: AsyncPeriodicTask,
+}
+
+impl AsyncLockWatchdog {
+    pub fn start(
+        mut lock_nodes: AsyncLockNodes,
+        lock_name: String,
+        lock_identifier: String,
+        lease_time: u64,
+        renew_interval: Duration,
+    ) -> Self {
+        let task = AsyncPeriodicTask::start(renew_interval, |ticker| async move {
+            while ticker.tick().await {
+                let mut extend_lock = extend_lock_script().key(&lock_name);
+                extend_lock.arg(&lock_identifier).arg(lease_time);
+                let extended = lock_nodes.invoke::<u8>(&extend_lock).await;
+
+                if !is_extended(extended, lock_nodes.quorum()) {
+                    break;
+                }
+            }
+        });
+
+        AsyncLockWatchdog { task }
+    }
+
+    pub async fn stop(self) {
+        self.task.stop().await;
+    }
+}
+
+/// Whether the lease was extended on enough nodes for the lock to still be held.
+fn is_extended(extended: Result<Vec<Option<u8>>, redis::RedisError>, quorum: usize) -> bool {
+    match extended {
+        Ok(extended) => extended.iter().filter(|reply| **reply == Some(1)).count() >= quorum,
+        Err(_) => false,
+    }
+}
@@ -1,22 +1,36 @@
 pub mod async_queue_lock;
+pub mod async_queue_lock_guard;
+mod lock_backoff;
+mod lock_lease;
+mod lock_nodes;
+mod lock_scripts;
+mod lock_watchdog;
+#[allow(clippy::module_inception)]
 pub mod queue_lock;
 pub mod queue_lock_builder;
+pub mod queue_lock_guard;
 
 // Write tests
 #[cfg(test)]
 mod tests {
+    use crate::error::RedisQueueError;
+    use crate::queue_lock::lock_backoff::LockBackoff;
+    use crate::queue_lock::lock_lease::LockLease;
     use crate::queue_lock::queue_lock_builder::QueueLockBuilder;
     use crate::test_utils::init_redis::{
         initialize_async_redis, initialize_redis, initialize_redis_client,
+        initialize_redis_clients, initialize_unreachable_redis_client,
     };
+    use redis::{AsyncCommands, Commands, SetExpiry, SetOptions};
     use std::sync::Arc;
 
-    #[tokio::test]
-    async fn initialize_async_queue_lock() {
+    #[test]
+    fn initialize_async_queue_lock() {
         let queue_lock = super::async_queue_lock::AsyncQueueLock::new(
             "initialize_async_queue_lock".to_string(),
-            initialize_async_redis().await,
-            None,
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
         );
         assert_eq!(
             queue_lock.get_lock_name(),
@@ -28,14 +42,16 @@ mod tests {
     async fn test_async_lock() {
         let mut queue_lock = super::async_queue_lock::AsyncQueueLock::new(
             "test_async_lock".to_string(),
-            initialize_async_redis().await,
-            None,
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
         );
         queue_lock
             .lock(|| async {
                 println!("Locked");
             })
-            .await;
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -46,8 +62,9 @@ mod tests {
         let h1 = tokio::spawn(async move {
             let mut queue_lock = super::async_queue_lock::AsyncQueueLock::new(
                 "test_async_concurrent_lock".to_string(),
-                initialize_async_redis().await,
-                None,
+                vec![initialize_redis_client()],
+                LockBackoff::default(),
+                LockLease::default(),
             );
             queue_lock
                 .lock(|| async {
@@ -58,7 +75,8 @@ mod tests {
                         panic!("Incremented second");
                     }
                 })
-                .await;
+                .await
+                .unwrap();
         });
 
         let increment_mutex_2 = increment_mutex.clone();
@@ -66,8 +84,9 @@ mod tests {
             tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
             let mut queue_lock2 = super::async_queue_lock::AsyncQueueLock::new(
                 "test_async_concurrent_lock".to_string(),
-                initialize_async_redis().await,
-                None,
+                vec![initialize_redis_client()],
+                LockBackoff::default(),
+                LockLease::default(),
             );
             queue_lock2
                 .lock(|| async {
@@ -78,7 +97,8 @@ mod tests {
                         panic!("Incremented first");
                     }
                 })
-                .await;
+                .await
+                .unwrap();
         });
 
         h1.await.unwrap();
@@ -89,8 +109,9 @@ mod tests {
     fn test_initialize_queue_lock() {
         let queue_lock = super::queue_lock::QueueLock::new(
             "test_initialize_queue_lock".to_string(),
-            initialize_redis(),
-            None,
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
         );
         assert_eq!(
             queue_lock.get_lock_name(),
@@ -100,11 +121,13 @@ mod tests {
 
     #[test]
     fn test_lock() {
-        let mut queue_lock =
-            super::queue_lock::QueueLock::new("test_lock".to_string(), initialize_redis(), None);
-        let result: u8 = queue_lock.lock(|| {
-            return 0;
-        });
+        let mut queue_lock = super::queue_lock::QueueLock::new(
+            "test_lock".to_string(),
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
+        );
+        let result: u8 = queue_lock.lock(|| 0).unwrap();
         assert_eq!(result, 0);
     }
 
@@ -116,8 +139,9 @@ mod tests {
         let h1 = std::thread::spawn(move || {
             let mut queue_lock = super::queue_lock::QueueLock::new(
                 "test_concurrent_lock".to_string(),
-                initialize_redis(),
-                None,
+                vec![initialize_redis_client()],
+                LockBackoff::default(),
+                LockLease::default(),
             );
 
             queue_lock.lock(|| {
@@ -127,7 +151,8 @@ mod tests {
                 } else {
                     panic!("Incremented second");
                 }
-            });
+            })
+            .unwrap();
         });
 
         let increment_mutex_2 = increment_mutex.clone();
@@ -135,8 +160,9 @@ mod tests {
             std::thread::sleep(std::time::Duration::from_secs(3));
             let mut queue_lock2 = super::queue_lock::QueueLock::new(
                 "test_concurrent_lock".to_string(),
-                initialize_redis(),
-                None,
+                vec![initialize_redis_client()],
+                LockBackoff::default(),
+                LockLease::default(),
             );
             queue_lock2.lock(|| {
                 let mut increment = increment_mutex_2.lock().unwrap();
@@ -145,7 +171,8 @@ mod tests {
                 } else {
                     panic!("Incremented first");
                 }
-            });
+            })
+            .unwrap();
         });
 
         h1.join().unwrap();
@@ -158,7 +185,8 @@ mod tests {
             .with_queue_name("test_queue_builder".to_string())
             .with_redis_client(initialize_redis_client())
             .with_retry_interval(100)
-            .build();
+            .build()
+            .unwrap();
         assert_eq!(
             queue_lock.get_lock_name(),
             "redis-queue:test_queue_builder:lock".to_string()
@@ -172,10 +200,766 @@ mod tests {
             .with_redis_client(initialize_redis_client())
             .with_retry_interval(100)
             .async_build()
-            .await;
+            .await
+            .unwrap();
         assert_eq!(
             queue_lock.get_lock_name(),
             "redis-queue:test_async_queue_builder:lock".to_string()
         );
     }
+
+    #[test]
+    fn test_queue_builder_without_redis_client() {
+        let result = QueueLockBuilder::default()
+            .with_queue_name("test_queue_builder_without_redis_client".to_string())
+            .build();
+        assert!(matches!(result, Err(RedisQueueError::Configuration(_))));
+    }
+
+    #[test]
+    fn test_lock_with_lease_time() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_with_lease_time".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(5000)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        let remaining_lease: i64 = queue_lock
+            .lock(|| redis_connection.pttl(lock_name).unwrap())
+            .unwrap();
+        assert!(remaining_lease > 0 && remaining_lease <= 5000);
+    }
+
+    #[test]
+    fn test_lock_reclaimed_after_lease() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_reclaimed_after_lease".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(500)
+            .build()
+            .unwrap();
+
+        // Leave the lock behind the way a holder that died before unlocking would.
+        let _: () = redis_connection
+            .set_options(
+                queue_lock.get_lock_name(),
+                "crashed-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(500)),
+            )
+            .unwrap();
+
+        let started_at = std::time::Instant::now();
+        queue_lock.lock(|| {}).unwrap();
+        assert!(started_at.elapsed() >= std::time::Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_with_lease_time() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_with_lease_time".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(5000)
+            .async_build()
+            .await
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        let remaining_lease: i64 = queue_lock
+            .lock(|| async { redis_connection.pttl(lock_name).await.unwrap() })
+            .await
+            .unwrap();
+        assert!(remaining_lease > 0 && remaining_lease <= 5000);
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_reclaimed_after_lease() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_reclaimed_after_lease".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(500)
+            .async_build()
+            .await
+            .unwrap();
+
+        // Leave the lock behind the way a holder that died before unlocking would.
+        let _: () = redis_connection
+            .set_options(
+                queue_lock.get_lock_name(),
+                "crashed-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(500)),
+            )
+            .await
+            .unwrap();
+
+        let started_at = std::time::Instant::now();
+        queue_lock.lock(|| async {}).await.unwrap();
+        assert!(started_at.elapsed() >= std::time::Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_lock_lost_before_unlock() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = super::queue_lock::QueueLock::new(
+            "test_lock_lost_before_unlock".to_string(),
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
+        );
+        let lock_name = queue_lock.get_lock_name();
+
+        let result = queue_lock.lock(|| {
+            // Another client takes the lock over, as if this holder's lease had run out.
+            let _: () = redis_connection.set(lock_name.clone(), "other-holder").unwrap();
+        });
+        assert!(matches!(result, Err(RedisQueueError::LockLost(_))));
+
+        let active_lock_identifier: String = redis_connection.get(lock_name.clone()).unwrap();
+        assert_eq!(active_lock_identifier, "other-holder");
+        let _: () = redis_connection.del(lock_name).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_lost_before_unlock() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = super::async_queue_lock::AsyncQueueLock::new(
+            "test_async_lock_lost_before_unlock".to_string(),
+            vec![initialize_redis_client()],
+            LockBackoff::default(),
+            LockLease::default(),
+        );
+        let lock_name = queue_lock.get_lock_name();
+
+        let result = queue_lock
+            .lock(|| async {
+                // Another client takes the lock over, as if this holder's lease had run out.
+                let _: () = redis_connection
+                    .set(lock_name.clone(), "other-holder")
+                    .await
+                    .unwrap();
+            })
+            .await;
+        assert!(matches!(result, Err(RedisQueueError::LockLost(_))));
+
+        let active_lock_identifier: String =
+            redis_connection.get(lock_name.clone()).await.unwrap();
+        assert_eq!(active_lock_identifier, "other-holder");
+        let _: () = redis_connection.del(lock_name).await.unwrap();
+    }
+
+    #[test]
+    fn test_backoff_delay_grows_until_capped() {
+        let backoff = LockBackoff::default()
+            .with_retry_interval(100)
+            .with_max_retry_interval(500)
+            .with_jitter(false);
+
+        let delays: Vec<u128> = (0..5).map(|attempt| backoff.delay(attempt).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(backoff.delay(u32::MAX).as_millis(), 500);
+    }
+
+    #[test]
+    fn test_backoff_delay_with_jitter() {
+        let backoff = LockBackoff::default()
+            .with_retry_interval(100)
+            .with_max_retry_interval(1000)
+            .with_jitter(true);
+
+        for _ in 0..100 {
+            let delay = backoff.delay(2).as_millis();
+            assert!((200..=400).contains(&delay));
+        }
+    }
+
+    #[test]
+    fn test_backoff_stops_after_max_wait() {
+        let backoff = LockBackoff::default().with_max_wait(50);
+        let started_at = std::time::Instant::now();
+
+        assert!(backoff.next_delay(0, started_at).unwrap().as_millis() <= 50);
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        assert!(backoff.next_delay(1, started_at).is_none());
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_timeout".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(300)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        let _: () = redis_connection
+            .set_options(
+                lock_name.clone(),
+                "other-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(5000)),
+            )
+            .unwrap();
+
+        let started_at = std::time::Instant::now();
+        let result = queue_lock.lock(|| {});
+        assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+        assert!(started_at.elapsed() >= std::time::Duration::from_millis(300));
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(2000));
+
+        let _: () = redis_connection.del(lock_name).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_timeout() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_timeout".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(300)
+            .async_build()
+            .await
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        let _: () = redis_connection
+            .set_options(
+                lock_name.clone(),
+                "other-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(5000)),
+            )
+            .await
+            .unwrap();
+
+        let started_at = std::time::Instant::now();
+        let result = queue_lock.lock(|| async {}).await;
+        assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+        assert!(started_at.elapsed() >= std::time::Duration::from_millis(300));
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(2000));
+
+        let _: () = redis_connection.del(lock_name).await.unwrap();
+    }
+
+    #[test]
+    fn test_lock_lease_renew_interval() {
+        assert!(LockLease::default().renew_interval().is_none());
+
+        let lease = LockLease::default()
+            .with_lease_time(900)
+            .with_watchdog(true);
+        assert_eq!(lease.renew_interval().unwrap().as_millis(), 300);
+    }
+
+    #[test]
+    fn test_lock_with_watchdog() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_with_watchdog".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(300)
+            .with_watchdog(true)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        // The critical section outlives the lease several times over.
+        let result = queue_lock.lock(|| {
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+            redis_connection.exists::<String, bool>(lock_name.clone()).unwrap()
+        });
+        assert!(result.unwrap());
+
+        let lock_exists: bool = redis_connection.exists(lock_name).unwrap();
+        assert!(!lock_exists);
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_with_watchdog() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_with_watchdog".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(300)
+            .with_watchdog(true)
+            .async_build()
+            .await
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        // The critical section outlives the lease several times over.
+        let result = queue_lock
+            .lock(|| async {
+                tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+                redis_connection
+                    .exists::<String, bool>(lock_name.clone())
+                    .await
+                    .unwrap()
+            })
+            .await;
+        assert!(result.unwrap());
+
+        let lock_exists: bool = redis_connection.exists(lock_name).await.unwrap();
+        assert!(!lock_exists);
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_woken_by_release() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_woken_by_release".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_retry_interval(5000)
+            .with_jitter(false);
+
+        let mut holder = lock_builder.clone().async_build().await.unwrap();
+        let (locked_sender, locked_receiver) = tokio::sync::oneshot::channel();
+        let holder_handle = tokio::spawn(async move {
+            holder
+                .lock(|| async move {
+                    locked_sender.send(()).unwrap();
+                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+                })
+                .await
+                .unwrap();
+        });
+        locked_receiver.await.unwrap();
+
+        let mut waiter = lock_builder.async_build().await.unwrap();
+        let started_at = std::time::Instant::now();
+        waiter.lock(|| async {}).await.unwrap();
+
+        // Polling alone would not retry before the 5 second backoff ran out.
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(2000));
+        holder_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_falls_back_to_polling() {
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_falls_back_to_polling".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_retry_interval(300)
+            .with_jitter(false)
+            .async_build()
+            .await
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        let _: () = redis_connection
+            .set_options(
+                lock_name.clone(),
+                "other-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(5000)),
+            )
+            .await
+            .unwrap();
+
+        // Release the lock without publishing a notification.
+        let releaser_handle = tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+            let _: () = redis_connection.del(lock_name).await.unwrap();
+        });
+
+        let started_at = std::time::Instant::now();
+        queue_lock.lock(|| async {}).await.unwrap();
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(2000));
+        releaser_handle.await.unwrap();
+    }
+
+    #[test]
+    fn test_lock_guard_released_on_drop() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_lock_guard_released_on_drop".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(200);
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = lock_builder.clone().build().unwrap();
+        let mut other_queue_lock = lock_builder.build().unwrap();
+
+        {
+            let guard = queue_lock.acquire().unwrap();
+            let lock_exists: bool = redis_connection.exists(guard.get_lock_name()).unwrap();
+            assert!(lock_exists);
+
+            let result = other_queue_lock.lock(|| {});
+            assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+        }
+
+        let lock_exists: bool = redis_connection.exists(queue_lock.get_lock_name()).unwrap();
+        assert!(!lock_exists);
+        other_queue_lock.lock(|| {}).unwrap();
+    }
+
+    #[test]
+    fn test_lock_guard_release() {
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_guard_release".to_string())
+            .with_redis_client(initialize_redis_client())
+            .build()
+            .unwrap();
+
+        let guard = queue_lock.acquire().unwrap();
+        guard.release().unwrap();
+
+        queue_lock.acquire().unwrap().release().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_guard_release() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_guard_release".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(200);
+        let mut redis_connection = initialize_async_redis().await;
+        let mut queue_lock = lock_builder.clone().async_build().await.unwrap();
+        let mut other_queue_lock = lock_builder.async_build().await.unwrap();
+
+        let guard = queue_lock.acquire().await.unwrap();
+        let lock_name = guard.get_lock_name();
+        let lock_exists: bool = redis_connection.exists(lock_name.clone()).await.unwrap();
+        assert!(lock_exists);
+
+        let result = other_queue_lock.lock(|| async {}).await;
+        assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+
+        guard.release().await.unwrap();
+
+        let lock_exists: bool = redis_connection.exists(lock_name).await.unwrap();
+        assert!(!lock_exists);
+        other_queue_lock.lock(|| async {}).await.unwrap();
+    }
+
+    #[test]
+    fn test_lock_released_when_closure_panics() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_lock_released_when_closure_panics".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(200);
+        let mut queue_lock = lock_builder.clone().build().unwrap();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            queue_lock.lock(|| panic!("Panicked while holding the lock"))
+        }));
+        assert!(result.is_err());
+
+        let mut other_queue_lock = lock_builder.build().unwrap();
+        other_queue_lock.lock(|| {}).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_released_when_closure_panics() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_released_when_closure_panics".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(1000);
+        let mut queue_lock = lock_builder.clone().async_build().await.unwrap();
+
+        let handle = tokio::spawn(async move {
+            queue_lock
+                .lock(|| async { panic!("Panicked while holding the lock") })
+                .await
+        });
+        assert!(handle.await.unwrap_err().is_panic());
+
+        let mut other_queue_lock = lock_builder.async_build().await.unwrap();
+        other_queue_lock.lock(|| async {}).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_released_when_future_dropped() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_released_when_future_dropped".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_max_wait(1000);
+        let mut queue_lock = lock_builder.clone().async_build().await.unwrap();
+
+        let result = tokio::time::timeout(
+            tokio::time::Duration::from_millis(200),
+            queue_lock.lock(|| async {
+                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+            }),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let mut other_queue_lock = lock_builder.async_build().await.unwrap();
+        let started_at = std::time::Instant::now();
+        other_queue_lock.lock(|| async {}).await.unwrap();
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_lock_fencing_token_increases() {
+        let mut redis_connection = initialize_redis();
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_fencing_token_increases".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(500)
+            .build()
+            .unwrap();
+        let fence_name = queue_lock.get_fence_name();
+
+        let guard = queue_lock.acquire().unwrap();
+        let first_token = guard.fencing_token();
+        guard.release().unwrap();
+
+        let guard = queue_lock.acquire().unwrap();
+        let second_token = guard.fencing_token();
+        assert!(second_token > first_token);
+
+        // A holder whose lease ran out keeps a token older than the one the next holder gets.
+        std::thread::sleep(std::time::Duration::from_millis(600));
+        let mut other_queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_lock_fencing_token_increases".to_string())
+            .with_redis_client(initialize_redis_client())
+            .build()
+            .unwrap();
+        let other_guard = other_queue_lock.acquire().unwrap();
+        assert!(other_guard.fencing_token() > second_token);
+
+        let current_token: u64 = redis_connection.get(fence_name).unwrap();
+        assert_eq!(current_token, other_guard.fencing_token());
+    }
+
+    #[tokio::test]
+    async fn test_async_lock_fencing_token_increases() {
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_lock_fencing_token_increases".to_string())
+            .with_redis_client(initialize_redis_client());
+        let mut queue_lock = lock_builder.clone().async_build().await.unwrap();
+        let mut other_queue_lock = lock_builder.async_build().await.unwrap();
+
+        let guard = queue_lock.acquire().await.unwrap();
+        let first_token = guard.fencing_token();
+        guard.release().await.unwrap();
+
+        let guard = other_queue_lock.acquire().await.unwrap();
+        assert!(guard.fencing_token() > first_token);
+        guard.release().await.unwrap();
+    }
+
+    fn hold_lock_on_node(redis_client: &redis::Client, lock_name: &str) {
+        let _: () = redis_client
+            .get_connection()
+            .unwrap()
+            .set_options(
+                lock_name,
+                "other-holder",
+                SetOptions::default().with_expiration(SetExpiry::PX(5000)),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_lock_lease_validity_time() {
+        let lease = LockLease::default().with_lease_time(1000);
+
+        assert_eq!(
+            lease.validity_time(std::time::Duration::from_millis(100)),
+            Some(std::time::Duration::from_millis(888))
+        );
+        assert_eq!(lease.validity_time(std::time::Duration::from_millis(990)), None);
+    }
+
+    #[test]
+    fn test_single_node_lock_with_short_lease() {
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_single_node_lock_with_short_lease".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(2)
+            .with_max_wait(1000)
+            .build()
+            .unwrap();
+
+        // No clock drift allowance is taken off a lease on a single node.
+        let guard = queue_lock.acquire().unwrap();
+        assert!(guard.fencing_token() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_single_node_lock_with_short_lease() {
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_single_node_lock_with_short_lease".to_string())
+            .with_redis_client(initialize_redis_client())
+            .with_lease_time(2)
+            .with_max_wait(1000)
+            .async_build()
+            .await
+            .unwrap();
+
+        let guard = queue_lock.acquire().await.unwrap();
+        assert!(guard.fencing_token() > 0);
+    }
+
+    #[test]
+    fn test_quorum_lock_with_minority_held() {
+        let redis_clients = initialize_redis_clients(3);
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_quorum_lock_with_minority_held".to_string())
+            .with_redis_clients(redis_clients.clone())
+            .with_max_wait(200)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+        hold_lock_on_node(&redis_clients[2], &lock_name);
+
+        let guard = queue_lock.acquire().unwrap();
+        for redis_client in &redis_clients[..2] {
+            let mut redis_connection = redis_client.get_connection().unwrap();
+            let lock_exists: bool = redis_connection.exists(&lock_name).unwrap();
+            assert!(lock_exists);
+        }
+        guard.release().unwrap();
+
+        let mut redis_connection = redis_clients[2].get_connection().unwrap();
+        let holder: Option<String> = redis_connection.get(&lock_name).unwrap();
+        assert_eq!(holder, Some("other-holder".to_string()));
+    }
+
+    #[test]
+    fn test_quorum_lock_timeout_with_majority_held() {
+        let redis_clients = initialize_redis_clients(3);
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_quorum_lock_timeout_with_majority_held".to_string())
+            .with_redis_clients(redis_clients.clone())
+            .with_max_wait(200)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+        hold_lock_on_node(&redis_clients[1], &lock_name);
+        hold_lock_on_node(&redis_clients[2], &lock_name);
+
+        let result = queue_lock.lock(|| {});
+        assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+
+        // The minority taken on the first node is given back.
+        let mut redis_connection = redis_clients[0].get_connection().unwrap();
+        let lock_exists: bool = redis_connection.exists(&lock_name).unwrap();
+        assert!(!lock_exists);
+    }
+
+    #[test]
+    fn test_quorum_lock_fencing_token_increases() {
+        let redis_clients = initialize_redis_clients(3);
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_quorum_lock_fencing_token_increases".to_string())
+            .with_redis_clients(redis_clients.clone())
+            .with_max_wait(200)
+            .build()
+            .unwrap();
+        let fence_name = queue_lock.get_fence_name();
+        for (node, redis_client) in redis_clients.iter().enumerate() {
+            let fencing_token = if node == 0 { 10 } else { 0 };
+            let mut redis_connection = redis_client.get_connection().unwrap();
+            let _: () = redis_connection.set(&fence_name, fencing_token).unwrap();
+        }
+
+        let guard = queue_lock.acquire().unwrap();
+        let first_token = guard.fencing_token();
+        assert_eq!(first_token, 11);
+        guard.release().unwrap();
+
+        // The next majority does not include the node that issued the first token.
+        hold_lock_on_node(&redis_clients[0], &queue_lock.get_lock_name());
+        let guard = queue_lock.acquire().unwrap();
+        assert!(guard.fencing_token() > first_token);
+        guard.release().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_quorum_lock() {
+        let redis_clients = initialize_redis_clients(3);
+        let mut queue_lock = QueueLockBuilder::default()
+            .with_queue_name("test_async_quorum_lock".to_string())
+            .with_redis_clients(redis_clients.clone())
+            .with_max_wait(200)
+            .async_build()
+            .await
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+        hold_lock_on_node(&redis_clients[0], &lock_name);
+
+        queue_lock.acquire().await.unwrap().release().await.unwrap();
+
+        // Losing the lock on a majority of nodes is reported on release.
+        let guard = queue_lock.acquire().await.unwrap();
+        for redis_client in &redis_clients[1..] {
+            let mut redis_connection =
+                redis_client.get_multiplexed_async_connection().await.unwrap();
+            let _: () = redis_connection.del(&lock_name).await.unwrap();
+        }
+        let result = guard.release().await;
+        assert!(matches!(result, Err(RedisQueueError::LockLost(_))));
+    }
+
+    #[test]
+    fn test_quorum_lock_with_unreachable_minority_node() {
+        let redis_clients = initialize_redis_clients(2);
+        let mut lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_quorum_lock_with_unreachable_minority_node".to_string())
+            .with_redis_clients(vec![
+                redis_clients[0].clone(),
+                initialize_unreachable_redis_client(),
+                redis_clients[1].clone(),
+            ])
+            .with_max_wait(200);
+        let mut queue_lock = lock_builder
+            .clone()
+            .with_lease_time(300)
+            .with_watchdog(true)
+            .build()
+            .unwrap();
+        let lock_name = queue_lock.get_lock_name();
+
+        // The watchdog keeps the lease on the reachable majority past its first expiry.
+        let guard = queue_lock.acquire().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        for redis_client in &redis_clients {
+            let mut redis_connection = redis_client.get_connection().unwrap();
+            let lock_exists: bool = redis_connection.exists(&lock_name).unwrap();
+            assert!(lock_exists);
+        }
+        guard.release().unwrap();
+
+        // Without the reachable nodes there is no majority to lock on.
+        lock_builder = lock_builder.with_redis_clients(vec![
+            redis_clients[0].clone(),
+            initialize_unreachable_redis_client(),
+            initialize_unreachable_redis_client(),
+        ]);
+        let result = lock_builder.build().unwrap().lock(|| {});
+        assert!(matches!(result, Err(RedisQueueError::LockTimeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_async_quorum_lock_with_unreachable_first_node() {
+        let redis_clients = initialize_redis_clients(2);
+        let lock_builder = QueueLockBuilder::default()
+            .with_queue_name("test_async_quorum_lock_with_unreachable_first_node".to_string())
+            .with_redis_clients(vec![
+                initialize_unreachable_redis_client(),
+                redis_clients[0].clone(),
+                redis_clients[1].clone(),
+            ])
+            .with_max_wait(2000);
+        let mut queue_lock = lock_builder.clone().async_build().await.unwrap();
+        let mut other_queue_lock = lock_builder.async_build().await.unwrap();
+
+        // The waiter listens for the release on the first node it can reach.
+        let guard = queue_lock.acquire().await.unwrap();
+        let waiter = tokio::spawn(async move {
+            let started_at = std::time::Instant::now();
+            other_queue_lock.acquire().await.unwrap().release().await.unwrap();
+            started_at.elapsed()
+        });
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        guard.release().await.unwrap();
+
+        assert!(waiter.await.unwrap() < std::time::Duration::from_millis(2000));
+    }
 }
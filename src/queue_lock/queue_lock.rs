@@ -1,62 +1,157 @@
-use redis::Connection;
-use redis::{Commands, ExistenceCheck, SetOptions};
+use std::time::Instant;
 use uuid::Uuid;
 
+use crate::error::RedisQueueError;
+use crate::queue_lock::lock_backoff::LockBackoff;
+use crate::queue_lock::lock_lease::LockLease;
+use crate::queue_lock::lock_nodes::LockNodes;
+use crate::queue_lock::lock_scripts::{
+    acquire_lock_script, raise_fence_script, release_lock_script,
+};
+use crate::queue_lock::lock_watchdog::LockWatchdog;
+use crate::queue_lock::queue_lock_guard::QueueLockGuard;
+
+/// A lock on one Redis node, or on a majority of several independent nodes (Redlock) when it
+/// is built with more than one client.
 pub struct QueueLock {
-    redis_connection: Connection,
-    retry_interval: u64,
+    redis_clients: Vec<redis::Client>,
+    lock_nodes: LockNodes,
+    backoff: LockBackoff,
+    lease: LockLease,
 
     queue_name: String,
 }
 
 impl QueueLock {
-    pub fn new(
+    pub(crate) fn new(
         queue_name: String,
-        redis_connection: Connection,
-        retry_interval: Option<u64>,
+        redis_clients: Vec<redis::Client>,
+        backoff: LockBackoff,
+        lease: LockLease,
     ) -> Self {
         QueueLock {
-            redis_connection,
-            retry_interval: retry_interval.unwrap_or(100),
-            queue_name: queue_name.to_string(),
+            lock_nodes: LockNodes::new(redis_clients.clone()),
+            redis_clients,
+            backoff,
+            lease,
+            queue_name,
         }
     }
-    pub fn lock<F, R>(&mut self, f: F) -> R
+
+    pub fn lock<F, R>(&mut self, f: F) -> Result<R, RedisQueueError>
     where
         F: FnOnce() -> R,
     {
-        let lock_identifier = Uuid::new_v4().to_string();
-        while !self.try_lock(lock_identifier.clone()) {
-            std::thread::sleep(std::time::Duration::from_millis(self.retry_interval));
-        }
+        // The guard also releases the lock if `f` panics.
+        let guard = self.acquire()?;
 
         let result = f();
 
-        self.unlock();
+        guard.release()?;
 
-        result
+        Ok(result)
+    }
+
+    pub fn acquire(&mut self) -> Result<QueueLockGuard<'_>, RedisQueueError> {
+        let lock_identifier = Uuid::new_v4().to_string();
+        let fencing_token = self.wait_for_lock(lock_identifier.clone())?;
+
+        let watchdog = self.start_watchdog(lock_identifier.clone());
+
+        Ok(QueueLockGuard::new(
+            self,
+            lock_identifier,
+            fencing_token,
+            watchdog,
+        ))
     }
 
     pub fn get_lock_name(&self) -> String {
         format!("redis-queue:{}:lock", self.queue_name)
     }
 
-    fn try_lock(&mut self, lock_identifier: String) -> bool {
-        let set_options = SetOptions::default()
-            .conditional_set(ExistenceCheck::NX)
-            .get(true);
+    pub fn get_release_channel_name(&self) -> String {
+        format!("redis-queue:{}:lock:released", self.queue_name)
+    }
+
+    pub fn get_fence_name(&self) -> String {
+        format!("redis-queue:{}:lock:fence", self.queue_name)
+    }
 
-        let active_lock_identifier: String = self
-            .redis_connection
-            .set_options(self.get_lock_name(), lock_identifier.clone(), set_options)
-            .unwrap_or("".to_string());
+    /// Releases the lock on every node, `false` when it was no longer held on a majority.
+    pub(crate) fn unlock(&mut self, lock_identifier: String) -> Result<bool, RedisQueueError> {
+        let mut release_lock = release_lock_script().key(self.get_lock_name());
+        release_lock
+            .arg(lock_identifier)
+            .arg(self.get_release_channel_name());
+        let released = self.lock_nodes.invoke::<u8>(&release_lock)?;
 
-        return active_lock_identifier == lock_identifier;
+        Ok(released.iter().filter(|reply| **reply == Some(1)).count() >= self.lock_nodes.quorum())
     }
 
-    fn unlock(&mut self) {
-        self.redis_connection
-            .del::<String, u8>(self.get_lock_name())
-            .unwrap();
+    fn wait_for_lock(&mut self, lock_identifier: String) -> Result<u64, RedisQueueError> {
+        let started_at = Instant::now();
+        let mut attempt = 0;
+        loop {
+            if let Some(fencing_token) = self.try_lock(lock_identifier.clone())? {
+                return Ok(fencing_token);
+            }
+
+            let delay = self
+                .backoff
+                .next_delay(attempt, started_at)
+                .ok_or_else(|| RedisQueueError::LockTimeout(self.get_lock_name()))?;
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn start_watchdog(&self, lock_identifier: String) -> Option<LockWatchdog> {
+        self.lease.renew_interval().map(|renew_interval| {
+            LockWatchdog::start(
+                LockNodes::new(self.redis_clients.clone()),
+                self.get_lock_name(),
+                lock_identifier,
+                self.lease.lease_time(),
+                renew_interval,
+            )
+        })
+    }
+
+    fn try_lock(&mut self, lock_identifier: String) -> Result<Option<u64>, RedisQueueError> {
+        let started_at = Instant::now();
+        let mut acquire_lock = acquire_lock_script().key(self.get_lock_name());
+        acquire_lock
+            .key(self.get_fence_name())
+            .arg(lock_identifier.clone())
+            .arg(self.lease.lease_time());
+        let fencing_tokens = self.lock_nodes.invoke::<u64>(&acquire_lock)?;
+
+        let acquired = fencing_tokens
+            .iter()
+            .filter(|fencing_token| fencing_token.is_some_and(|token| token > 0))
+            .count();
+        if acquired == 0 {
+            return Ok(None);
+        }
+
+        // With several nodes, a lock held on too few of them, or that took too long to take, is
+        // given back. A single node holds the lock for its whole lease.
+        if self.lock_nodes.node_count() > 1
+            && (acquired < self.lock_nodes.quorum()
+                || self.lease.validity_time(started_at.elapsed()).is_none())
+        {
+            self.unlock(lock_identifier)?;
+            return Ok(None);
+        }
+
+        let fencing_token = fencing_tokens.iter().flatten().copied().max().unwrap_or_default();
+        if self.lock_nodes.node_count() > 1 {
+            let mut raise_fence = raise_fence_script().key(self.get_fence_name());
+            raise_fence.arg(fencing_token);
+            self.lock_nodes.invoke::<u8>(&raise_fence)?;
+        }
+
+        Ok(Some(fencing_token))
     }
 }
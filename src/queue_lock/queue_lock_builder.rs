@@ -1,62 +1,97 @@
+use crate::error::RedisQueueError;
 use crate::queue_lock::async_queue_lock::AsyncQueueLock;
+use crate::queue_lock::lock_backoff::LockBackoff;
+use crate::queue_lock::lock_lease::LockLease;
 use crate::queue_lock::queue_lock::QueueLock;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct QueueLockBuilder {
     queue_name: Option<String>,
-    redis_client: Option<redis::Client>,
-    retry_interval: Option<u64>,
+    redis_clients: Vec<redis::Client>,
+    backoff: LockBackoff,
+    lease: LockLease,
 }
 
 impl QueueLockBuilder {
-    pub fn default() -> Self {
-        QueueLockBuilder {
-            queue_name: None,
-            redis_client: None,
-            retry_interval: None,
-        }
-    }
-
     pub fn with_queue_name(mut self, queue_name: String) -> Self {
         self.queue_name = Some(queue_name);
         self
     }
 
     pub fn with_redis_client(mut self, redis_client: redis::Client) -> Self {
-        self.redis_client = Some(redis_client);
+        self.redis_clients = vec![redis_client];
+        self
+    }
+
+    /// Locks on a majority of these independent nodes instead of a single one. A node that
+    /// cannot be reached only counts against the quorum, and is connected to again on the next
+    /// attempt.
+    pub fn with_redis_clients(mut self, redis_clients: Vec<redis::Client>) -> Self {
+        self.redis_clients = redis_clients;
         self
     }
 
     pub fn with_retry_interval(mut self, retry_interval: u64) -> Self {
-        self.retry_interval = Some(retry_interval);
+        self.backoff = self.backoff.with_retry_interval(retry_interval);
         self
     }
 
-    pub fn build(self) -> QueueLock {
-        if self.redis_client.is_none() {
-            panic!("Redis Client is required to build QueueLock");
-        }
+    pub fn with_max_retry_interval(mut self, max_retry_interval: u64) -> Self {
+        self.backoff = self.backoff.with_max_retry_interval(max_retry_interval);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.backoff = self.backoff.with_jitter(jitter);
+        self
+    }
+
+    pub fn with_max_wait(mut self, max_wait: u64) -> Self {
+        self.backoff = self.backoff.with_max_wait(max_wait);
+        self
+    }
+
+    pub fn with_lease_time(mut self, lease_time: u64) -> Self {
+        self.lease = self.lease.with_lease_time(lease_time);
+        self
+    }
+
+    pub fn with_watchdog(mut self, watchdog: bool) -> Self {
+        self.lease = self.lease.with_watchdog(watchdog);
+        self
+    }
+
+    pub fn with_clock_drift_factor(mut self, clock_drift_factor: f64) -> Self {
+        self.lease = self.lease.with_clock_drift_factor(clock_drift_factor);
+        self
+    }
+
+    pub fn build(self) -> Result<QueueLock, RedisQueueError> {
+        let (queue_name, redis_clients) = self.required_fields("QueueLock")?;
+
+        Ok(QueueLock::new(queue_name, redis_clients, self.backoff.clone(), self.lease))
+    }
+
+    pub async fn async_build(self) -> Result<AsyncQueueLock, RedisQueueError> {
+        let (queue_name, redis_clients) = self.required_fields("AsyncQueueLock")?;
 
-        return QueueLock::new(
-            self.queue_name.unwrap(),
-            self.redis_client.unwrap().get_connection().unwrap(),
-            self.retry_interval,
-        );
+        Ok(AsyncQueueLock::new(queue_name, redis_clients, self.backoff.clone(), self.lease))
     }
 
-    pub async fn async_build(self) -> AsyncQueueLock {
-        if self.redis_client.is_none() {
-            panic!("Redis Client is required to build AsyncQueueLock");
+    fn required_fields(
+        &self,
+        lock_type: &str,
+    ) -> Result<(String, Vec<redis::Client>), RedisQueueError> {
+        let queue_name = self.queue_name.clone().ok_or_else(|| {
+            RedisQueueError::Configuration(format!("Queue Name is required to build {}", lock_type))
+        })?;
+        if self.redis_clients.is_empty() {
+            return Err(RedisQueueError::Configuration(format!(
+                "Redis Client is required to build {}",
+                lock_type
+            )));
         }
 
-        return AsyncQueueLock::new(
-            self.queue_name.unwrap(),
-            self.redis_client
-                .unwrap()
-                .get_multiplexed_async_connection()
-                .await
-                .unwrap(),
-            self.retry_interval,
-        );
+        Ok((queue_name, self.redis_clients.clone()))
     }
 }
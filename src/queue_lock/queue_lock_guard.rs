@@ -0,0 +1,67 @@
+use crate::error::RedisQueueError;
+use crate::queue_lock::lock_watchdog::LockWatchdog;
+use crate::queue_lock::queue_lock::QueueLock;
+
+/// Holds a [`QueueLock`] until it is released or dropped.
+pub struct QueueLockGuard<'a> {
+    queue_lock: &'a mut QueueLock,
+    lock_identifier: String,
+    fencing_token: u64,
+    watchdog: Option<LockWatchdog>,
+    released: bool,
+}
+
+impl<'a> QueueLockGuard<'a> {
+    pub(crate) fn new(
+        queue_lock: &'a mut QueueLock,
+        lock_identifier: String,
+        fencing_token: u64,
+        watchdog: Option<LockWatchdog>,
+    ) -> Self {
+        QueueLockGuard {
+            queue_lock,
+            lock_identifier,
+            fencing_token,
+            watchdog,
+            released: false,
+        }
+    }
+
+    pub fn get_lock_name(&self) -> String {
+        self.queue_lock.get_lock_name()
+    }
+
+    /// Token issued for this acquisition, greater than the token of any earlier holder.
+    pub fn fencing_token(&self) -> u64 {
+        self.fencing_token
+    }
+
+    /// Releases the lock, reporting `LockLost` if its lease ran out and another client took it.
+    pub fn release(mut self) -> Result<(), RedisQueueError> {
+        self.unlock()
+    }
+
+    fn unlock(&mut self) -> Result<(), RedisQueueError> {
+        if self.released {
+            return Ok(());
+        }
+        self.released = true;
+
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.stop();
+        }
+
+        if !self.queue_lock.unlock(self.lock_identifier.clone())? {
+            return Err(RedisQueueError::LockLost(self.get_lock_name()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for QueueLockGuard<'_> {
+    fn drop(&mut self) {
+        // Errors cannot be reported from drop, the lease expires the lock if this fails.
+        let _ = self.unlock();
+    }
+}
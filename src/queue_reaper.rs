@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::periodic_task::PeriodicTask;
+use crate::redis_queue::RedisQueue;
+
+/// Background thread that periodically hands expired reservations of a [`RedisQueue`] back to
+/// the queue. It stops when it is stopped or dropped, or on the first error that retrying
+/// would not fix.
+pub struct QueueReaper {
+    task: PeriodicTask,
+}
+
+impl QueueReaper {
+    pub(crate) fn start<T>(mut redis_queue: RedisQueue<T>, interval: Duration) -> Self
+    where T: Clone + Serialize + for<'de> Deserialize<'de> + Send + 'static {
+        let task = PeriodicTask::start(interval, move |ticker| {
+            while ticker.tick() {
+                match redis_queue.requeue_expired() {
+                    Err(error) if !error.is_transient() => break,
+                    _ => {}
+                }
+            }
+        });
+
+        QueueReaper { task }
+    }
+
+    pub fn stop(self) {
+        self.task.stop();
+    }
+}
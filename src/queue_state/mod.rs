@@ -1 +1,5 @@
+pub mod queue_clock;
 pub mod queue_element;
+pub mod queue_keys;
+pub mod queue_scripts;
+pub mod stream_consumer;
@@ -1,10 +1,30 @@
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+
+// Elements written before `data` was stored as a JSON string have no format, and hold `data`
+// as plain JSON.
+const ENCODED_DATA_FORMAT: u32 = 1;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(bound(deserialize = "T: DeserializeOwned"), try_from = "StoredQueueElement")]
 pub struct QueueElement<T: Clone + Serialize> {
     id: String,
+    // Kept as a JSON string so the queue scripts can relink elements without re-encoding the data.
+    #[serde(serialize_with = "to_json")]
     data: T,
     next: Option<String>,
+    format: u32,
+    // Counted by the queue scripts each time a reserved element is handed back to the queue.
+    #[serde(default)]
+    redeliveries: u32,
+    #[serde(default)]
+    priority: i32,
+    // Unix time in milliseconds after which `pop` discards the element instead of delivering it.
+    #[serde(default)]
+    expires_at: Option<u64>,
+    // Set by the queue scripts on an element moved to the dead letters because it expired.
+    #[serde(default)]
+    expired: bool,
 }
 
 impl<T: Clone + Serialize> QueueElement<T> {
@@ -13,9 +33,19 @@ impl<T: Clone + Serialize> QueueElement<T> {
             id: uuid::Uuid::new_v4().to_string(),
             data,
             next: None,
+            format: ENCODED_DATA_FORMAT,
+            redeliveries: 0,
+            priority: 0,
+            expires_at: None,
+            expired: false,
         }
     }
 
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
     pub fn get_id(&self) -> String {
         self.id.clone()
     }
@@ -24,11 +54,77 @@ impl<T: Clone + Serialize> QueueElement<T> {
         self.data.clone()
     }
 
-    pub fn set_next(&mut self, next: Option<String>) {
-        self.next = next;
+    pub fn with_expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn set_next(&mut self, next: String) {
+        self.next = Some(next);
+    }
+
+    pub fn with_redeliveries(mut self, redeliveries: u32) -> Self {
+        self.redeliveries = redeliveries;
+        self
+    }
+
+    pub fn get_redeliveries(&self) -> u32 {
+        self.redeliveries
+    }
+
+    pub fn get_priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn get_expires_at(&self) -> Option<u64> {
+        self.expires_at
     }
 
-    pub fn get_next(&self) -> Option<String> {
-        self.next.clone()
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+}
+
+fn to_json<T: Serialize, S: Serializer>(data: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    let json = serde_json::to_string(data).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&json)
+}
+
+#[derive(Deserialize)]
+struct StoredQueueElement {
+    id: String,
+    data: serde_json::Value,
+    next: Option<String>,
+    #[serde(default)]
+    format: u32,
+    #[serde(default)]
+    redeliveries: u32,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    expires_at: Option<u64>,
+    #[serde(default)]
+    expired: bool,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> TryFrom<StoredQueueElement> for QueueElement<T> {
+    type Error = serde_json::Error;
+
+    fn try_from(stored: StoredQueueElement) -> Result<Self, Self::Error> {
+        let data = match stored.format {
+            0 => serde_json::from_value(stored.data)?,
+            _ => serde_json::from_str(&serde_json::from_value::<String>(stored.data)?)?,
+        };
+
+        Ok(QueueElement {
+            id: stored.id,
+            data,
+            next: stored.next,
+            format: ENCODED_DATA_FORMAT,
+            redeliveries: stored.redeliveries,
+            priority: stored.priority,
+            expires_at: stored.expires_at,
+            expired: stored.expired,
+        })
     }
 }
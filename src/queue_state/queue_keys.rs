@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(300);
+// Redis rejects a dedup key that would expire in less than a millisecond.
+const MIN_DEDUP_WINDOW: Duration = Duration::from_millis(1);
+
+/// How long the dedup keys of a queue are kept for the requested window.
+pub fn dedup_window(dedup_window: Duration) -> Duration {
+    dedup_window.max(MIN_DEDUP_WINDOW)
+}
+
+/// Names of the Redis keys a queue is stored under.
+#[derive(Clone)]
+pub struct QueueKeys {
+    queue_name: String,
+}
+
+impl QueueKeys {
+    pub fn new(queue_name: &str) -> Self {
+        QueueKeys {
+            queue_name: queue_name.to_string(),
+        }
+    }
+
+    pub fn element_prefix(&self) -> String {
+        format!("redis-queue:{}:element:", self.queue_name)
+    }
+
+    pub fn first_element(&self) -> String {
+        format!("redis-queue:{}:state:first", self.queue_name)
+    }
+
+    pub fn last_element(&self) -> String {
+        format!("redis-queue:{}:state:last", self.queue_name)
+    }
+
+    pub fn processing(&self) -> String {
+        format!("redis-queue:{}:processing", self.queue_name)
+    }
+
+    /// Sorted set of the reserved element ids, scored by when they are redelivered.
+    pub fn processing_deadlines(&self) -> String {
+        format!("redis-queue:{}:processing:deadlines", self.queue_name)
+    }
+
+    /// Number of elements waiting in the queue, kept up to date by every push and pop.
+    pub fn length(&self) -> String {
+        format!("redis-queue:{}:length", self.queue_name)
+    }
+
+    pub fn dead_letters(&self) -> String {
+        format!("redis-queue:{}:dead", self.queue_name)
+    }
+
+    /// Sorted set of the delayed elements, scored by when they are due.
+    pub fn delayed(&self) -> String {
+        format!("redis-queue:{}:delayed", self.queue_name)
+    }
+
+    /// Sorted set of the priority levels that hold elements.
+    pub fn priorities(&self) -> String {
+        format!("redis-queue:{}:priorities", self.queue_name)
+    }
+
+    pub fn priority_prefix(&self) -> String {
+        format!("redis-queue:{}:priority:", self.queue_name)
+    }
+
+    pub fn priority_list(&self, priority: i32) -> String {
+        format!("{}{}", self.priority_prefix(), priority)
+    }
+
+    pub fn dedup(&self, dedup_key: &str) -> String {
+        format!("redis-queue:{}:dedup:{}", self.queue_name, dedup_key)
+    }
+
+    pub fn list(&self) -> String {
+        format!("redis-queue:{}:list", self.queue_name)
+    }
+
+    pub fn stream(&self) -> String {
+        format!("redis-queue:{}:stream", self.queue_name)
+    }
+
+    pub fn push_channel(&self) -> String {
+        format!("redis-queue:{}:pushed", self.queue_name)
+    }
+}
@@ -0,0 +1,614 @@
+use std::sync::OnceLock;
+
+use redis::{Script, ScriptInvocation};
+
+use crate::queue_backend::QueueBackend;
+use crate::queue_state::queue_keys::QueueKeys;
+
+// Links elements in after the last one, returning an error message when the queue is corrupted.
+const APPEND_ELEMENT_FUNCTION: &str = r#"
+-- Elements written before `data` was stored as a JSON string hold it as plain JSON, which would
+-- not survive a cjson round trip. Their data is cut out of the raw element as is, and stored as a
+-- string like in the current format. Returns false when the element cannot be read.
+local function upgrade_element(element_data)
+    local element = cjson.decode(element_data)
+    if element['format'] then
+        return element_data
+    end
+
+    local prefix = '{"id":' .. cjson.encode(element['id']) .. ',"data":'
+    local suffix_start = string.find(element_data, ',"next":[^,]*}$')
+    if string.sub(element_data, 1, #prefix) ~= prefix or not suffix_start then
+        return false
+    end
+
+    element['data'] = string.sub(element_data, #prefix + 1, suffix_start - 1)
+    element['format'] = 1
+    return cjson.encode(element)
+end
+
+local function link_after_last(first_key, last_key, element_prefix, element_id)
+    local last_element_id = redis.call('GET', last_key)
+    if not last_element_id then
+        redis.call('SET', first_key, element_id)
+        return
+    end
+
+    local last_element_key = element_prefix .. last_element_id
+    local last_element = redis.call('GET', last_element_key)
+    if not last_element then
+        return 'CORRUPTED last element ' .. last_element_id .. ' is missing'
+    end
+
+    last_element = upgrade_element(last_element)
+    if not last_element then
+        return 'CORRUPTED last element ' .. last_element_id .. ' cannot be read'
+    end
+    last_element = cjson.decode(last_element)
+    last_element['next'] = element_id
+    redis.call('SET', last_element_key, cjson.encode(last_element))
+end
+
+local function append_element(first_key, last_key, element_prefix, element)
+    local corrupted = link_after_last(first_key, last_key, element_prefix, element['id'])
+    if corrupted then
+        return corrupted
+    end
+
+    element['next'] = cjson.null
+    redis.call('SET', element_prefix .. element['id'], cjson.encode(element))
+    redis.call('SET', last_key, element['id'])
+end
+"#;
+
+// KEYS[1]: state:first, KEYS[2]: state:last, KEYS[3]: length
+// ARGV[1]: element key prefix, ARGV[2]: element, ARGV[3]: push channel
+const PUSH_ELEMENT_SCRIPT: &str = r#"
+local element = cjson.decode(ARGV[2])
+local corrupted = append_element(KEYS[1], KEYS[2], ARGV[1], element)
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+redis.call('INCR', KEYS[3])
+
+redis.call('PUBLISH', ARGV[3], element['id'])
+return 1
+"#;
+
+pub fn push_element_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| {
+        Script::new(&format!("{}{}", APPEND_ELEMENT_FUNCTION, PUSH_ELEMENT_SCRIPT))
+    })
+}
+
+// Appends a batch of elements that are already linked to each other, in order.
+// KEYS[1]: state:first, KEYS[2]: state:last, KEYS[3]: length
+// ARGV[1]: element key prefix, ARGV[2]: push channel
+// ARGV[3..]: element id and element pairs
+const PUSH_ELEMENTS_SCRIPT: &str = r#"
+local corrupted = link_after_last(KEYS[1], KEYS[2], ARGV[1], ARGV[3])
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+
+for i = 3, #ARGV, 2 do
+    redis.call('SET', ARGV[1] .. ARGV[i], ARGV[i + 1])
+end
+redis.call('SET', KEYS[2], ARGV[#ARGV - 1])
+redis.call('INCRBY', KEYS[3], (#ARGV - 2) / 2)
+redis.call('PUBLISH', ARGV[2], ARGV[3])
+return 1
+"#;
+
+pub fn push_elements_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| {
+        Script::new(&format!("{}{}", APPEND_ELEMENT_FUNCTION, PUSH_ELEMENTS_SCRIPT))
+    })
+}
+
+// KEYS[1]: processing, KEYS[2]: processing deadlines
+// ARGV[1]: element id, ARGV[2]: redeliveries the element was reserved with
+const ACK_ELEMENT_SCRIPT: &str = r#"
+local element = redis.call('HGET', KEYS[1], ARGV[1])
+if not element then
+    return 0
+end
+-- An element that expired and was reserved again belongs to its new consumer.
+if (tonumber(cjson.decode(element)['redeliveries']) or 0) ~= tonumber(ARGV[2]) then
+    return 0
+end
+
+redis.call('HDEL', KEYS[1], ARGV[1])
+redis.call('ZREM', KEYS[2], ARGV[1])
+return 1
+"#;
+
+pub fn ack_element_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(ACK_ELEMENT_SCRIPT))
+}
+
+// Hands a reserved stream entry back by adding its element again at the end of the stream, and
+// acknowledging the entry in the same step.
+// KEYS[1]: stream
+// ARGV[1]: consumer group, ARGV[2]: entry id, ARGV[3]: element, ARGV[4]: push channel
+const NACK_STREAM_ENTRY_SCRIPT: &str = r#"
+if redis.call('XACK', KEYS[1], ARGV[1], ARGV[2]) == 0 then
+    return 0
+end
+
+redis.call('XADD', KEYS[1], '*', 'element', ARGV[3])
+redis.call('PUBLISH', ARGV[4], cjson.decode(ARGV[3])['id'])
+return 1
+"#;
+
+pub fn nack_stream_entry_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| Script::new(NACK_STREAM_ENTRY_SCRIPT))
+}
+
+// The scripts below take the keys and arguments set up by `queue_invocation`, followed by their
+// own arguments.
+// KEYS[1]: processing, KEYS[2]: processing deadlines, KEYS[3]: dead letters, KEYS[4]: delayed
+// KEYS[5]: length, KEYS[6]: list, priority levels, stream, or state:first and KEYS[7]: state:last
+// ARGV[1]: backend, ARGV[2]: element key prefix, or priority list key prefix
+// ARGV[3]: push channel, ARGV[4]: max delivery attempts, empty for no limit
+//
+// The functions work on whichever backend ARGV[1] names, keep the length up to date, and return
+// an error message when the queue is corrupted.
+const QUEUE_FUNCTIONS: &str = r#"
+local function count_taken()
+    -- A queue that held elements before its length was kept starts counting from zero.
+    if (tonumber(redis.call('GET', KEYS[5])) or 0) > 0 then
+        redis.call('DECR', KEYS[5])
+    end
+end
+
+local function push_back(element)
+    if ARGV[1] == 'stream' then
+        -- Streams are counted by XLEN, not by the length key.
+        redis.call('XADD', KEYS[6], '*', 'element', cjson.encode(element))
+        return
+    elseif ARGV[1] == 'list' then
+        redis.call('LPUSH', KEYS[6], cjson.encode(element))
+    elseif ARGV[1] == 'priority' then
+        local priority = element['priority'] or 0
+        redis.call('LPUSH', ARGV[2] .. priority, cjson.encode(element))
+        redis.call('ZADD', KEYS[6], priority, priority)
+    else
+        local corrupted = append_element(KEYS[6], KEYS[7], ARGV[2], element)
+        if corrupted then
+            return corrupted
+        end
+    end
+    redis.call('INCR', KEYS[5])
+end
+
+local function push_front(element)
+    if ARGV[1] == 'list' then
+        redis.call('RPUSH', KEYS[6], cjson.encode(element))
+    elseif ARGV[1] == 'priority' then
+        local priority = element['priority'] or 0
+        redis.call('RPUSH', ARGV[2] .. priority, cjson.encode(element))
+        redis.call('ZADD', KEYS[6], priority, priority)
+    else
+        local first_element_id = redis.call('GET', KEYS[6])
+        element['next'] = first_element_id or cjson.null
+        redis.call('SET', ARGV[2] .. element['id'], cjson.encode(element))
+        redis.call('SET', KEYS[6], element['id'])
+        if not first_element_id then
+            redis.call('SET', KEYS[7], element['id'])
+        end
+    end
+    redis.call('INCR', KEYS[5])
+end
+
+-- Returns the element taken off the front, or false when the queue is empty.
+local function pop_front()
+    if ARGV[1] == 'list' then
+        local element = redis.call('RPOP', KEYS[6])
+        if element then
+            count_taken()
+        end
+        return element
+    elseif ARGV[1] == 'priority' then
+        local priority = redis.call('ZREVRANGE', KEYS[6], 0, 0)[1]
+        if not priority then
+            return false
+        end
+
+        local element = redis.call('RPOP', ARGV[2] .. priority)
+        if redis.call('LLEN', ARGV[2] .. priority) == 0 then
+            redis.call('ZREM', KEYS[6], priority)
+        end
+        if not element then
+            return false, 'CORRUPTED priority level ' .. priority .. ' is empty'
+        end
+        count_taken()
+        return element
+    end
+
+    local first_element_id = redis.call('GET', KEYS[6])
+    if not first_element_id then
+        return false
+    end
+
+    local first_element_key = ARGV[2] .. first_element_id
+    local element = redis.call('GET', first_element_key)
+    if not element then
+        return false, 'CORRUPTED first element ' .. first_element_id .. ' is missing'
+    end
+
+    element = upgrade_element(element)
+    if not element then
+        return false, 'CORRUPTED first element ' .. first_element_id .. ' cannot be read'
+    end
+
+    local next_element_id = cjson.decode(element)['next']
+    if next_element_id and next_element_id ~= cjson.null then
+        redis.call('SET', KEYS[6], next_element_id)
+    else
+        redis.call('DEL', KEYS[6], KEYS[7])
+    end
+    redis.call('DEL', first_element_key)
+    count_taken()
+    return element
+end
+
+-- Takes the first element that has not expired by `now` off the front of the queue. Expired
+-- elements before it are discarded, or moved to the dead letters.
+local function pop_unexpired(now, dead_letter_expired)
+    while true do
+        local element, corrupted = pop_front()
+        if not element then
+            return false, corrupted
+        end
+
+        local decoded = cjson.decode(element)
+        local expires_at = decoded['expires_at']
+        if not expires_at or expires_at == cjson.null or tonumber(expires_at) > tonumber(now) then
+            return element
+        end
+        if dead_letter_expired then
+            decoded['expired'] = true
+            redis.call('HSET', KEYS[3], decoded['id'], cjson.encode(decoded))
+        end
+    end
+end
+
+-- Moves the delayed elements that are due by `now` to the end of the queue.
+local function promote_due_elements(now)
+    local due_elements = redis.call('ZRANGEBYSCORE', KEYS[4], '-inf', now)
+    for _, element in ipairs(due_elements) do
+        local corrupted = push_back(cjson.decode(element))
+        if corrupted then
+            return corrupted
+        end
+        redis.call('ZREM', KEYS[4], element)
+    end
+end
+
+-- Puts a reserved element back at the front of the queue, counting the redelivery on it, or
+-- moves it to the dead letters once it used up its delivery attempts.
+local function requeue_element(element_id, reserved_redeliveries)
+    local element_data = redis.call('HGET', KEYS[1], element_id)
+    if not element_data then
+        redis.call('ZREM', KEYS[2], element_id)
+        return false
+    end
+
+    local element = cjson.decode(element_data)
+    local redeliveries = tonumber(element['redeliveries']) or 0
+    -- An element that expired and was reserved again belongs to its new consumer.
+    if reserved_redeliveries and redeliveries ~= tonumber(reserved_redeliveries) then
+        return false
+    end
+    redis.call('HDEL', KEYS[1], element_id)
+    redis.call('ZREM', KEYS[2], element_id)
+
+    if ARGV[4] ~= '' and redeliveries + 1 >= tonumber(ARGV[4]) then
+        redis.call('HSET', KEYS[3], element_id, element_data)
+        return true
+    end
+
+    element['redeliveries'] = redeliveries + 1
+    push_front(element)
+    redis.call('PUBLISH', ARGV[3], element_id)
+    return true
+end
+"#;
+
+fn queue_script(body: &str) -> Script {
+    Script::new(&format!("{}{}{}", APPEND_ELEMENT_FUNCTION, QUEUE_FUNCTIONS, body))
+}
+
+// Takes the first element of the queue that has not expired, and keeps it in the processing set
+// when reserving.
+// ARGV[5]: current time, ARGV[6]: '1' to reserve the element
+// ARGV[7]: redelivery deadline, empty for none, ARGV[8]: '1' to dead-letter expired elements
+const TAKE_ELEMENT_SCRIPT: &str = r#"
+local corrupted = promote_due_elements(ARGV[5])
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+
+local element
+element, corrupted = pop_unexpired(ARGV[5], ARGV[8] == '1')
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+if not element then
+    return false
+end
+
+if ARGV[6] == '1' then
+    local element_id = cjson.decode(element)['id']
+    redis.call('HSET', KEYS[1], element_id, element)
+    if ARGV[7] ~= '' then
+        redis.call('ZADD', KEYS[2], ARGV[7], element_id)
+    end
+end
+return element
+"#;
+
+pub fn take_element_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(TAKE_ELEMENT_SCRIPT))
+}
+
+// Takes up to a number of elements off the front of the queue that have not expired, in order.
+// ARGV[5]: current time, ARGV[6]: '1' to dead-letter expired elements, ARGV[7]: element count
+const TAKE_ELEMENTS_SCRIPT: &str = r#"
+local corrupted = promote_due_elements(ARGV[5])
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+
+local elements = {}
+while #elements < tonumber(ARGV[7]) do
+    local element
+    element, corrupted = pop_unexpired(ARGV[5], ARGV[6] == '1')
+    if corrupted then
+        return redis.error_reply(corrupted)
+    end
+    if not element then
+        break
+    end
+    table.insert(elements, element)
+end
+return elements
+"#;
+
+pub fn take_elements_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(TAKE_ELEMENTS_SCRIPT))
+}
+
+// ARGV[5]: element id, ARGV[6]: redeliveries the element was reserved with
+const NACK_ELEMENT_SCRIPT: &str = r#"
+if requeue_element(ARGV[5], ARGV[6]) then
+    return 1
+end
+return 0
+"#;
+
+pub fn nack_element_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(NACK_ELEMENT_SCRIPT))
+}
+
+// ARGV[5]: current time
+const REQUEUE_EXPIRED_SCRIPT: &str = r#"
+local expired_ids = redis.call('ZRANGEBYSCORE', KEYS[2], '-inf', ARGV[5])
+local requeued = 0
+-- Newest first, so the element reserved the longest ago ends up at the front.
+for i = #expired_ids, 1, -1 do
+    if requeue_element(expired_ids[i]) then
+        requeued = requeued + 1
+    end
+end
+return requeued
+"#;
+
+pub fn requeue_expired_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(REQUEUE_EXPIRED_SCRIPT))
+}
+
+// Reads the elements that have not expired from a position in the queue on, without taking them.
+// Delayed elements that are due are read where the next pop would move them to.
+// ARGV[5]: current time, ARGV[6]: start position, ARGV[7]: element count
+const PEEK_ELEMENTS_SCRIPT: &str = r#"
+local now, start, count = tonumber(ARGV[5]), tonumber(ARGV[6]), tonumber(ARGV[7])
+local elements = {}
+local position = 0
+
+-- Returns true once the range is full.
+local function visit(element)
+    local expires_at = cjson.decode(element)['expires_at']
+    if expires_at and expires_at ~= cjson.null and tonumber(expires_at) <= now then
+        return false
+    end
+
+    if position >= start then
+        table.insert(elements, element)
+    end
+    position = position + 1
+    return #elements >= count
+end
+
+-- Lists are read a page at a time from their right end, which is their front.
+local function visit_list(key)
+    local offset = 0
+    while true do
+        local page = redis.call('LRANGE', key, -(offset + 100), -(offset + 1))
+        for i = #page, 1, -1 do
+            if visit(page[i]) then
+                return true
+            end
+        end
+        if #page < 100 then
+            return false
+        end
+        offset = offset + 100
+    end
+end
+
+local function visit_all(elements_to_visit)
+    for _, element in ipairs(elements_to_visit) do
+        if visit(element) then
+            return true
+        end
+    end
+    return false
+end
+
+local due_elements = redis.call('ZRANGEBYSCORE', KEYS[4], '-inf', now)
+
+if ARGV[1] == 'list' then
+    if not visit_list(KEYS[6]) then
+        visit_all(due_elements)
+    end
+elseif ARGV[1] == 'priority' then
+    -- Due elements go to the end of their own priority level, which may not exist yet.
+    local levels, level_keys, due_by_level = {}, {}, {}
+    for _, priority in ipairs(redis.call('ZREVRANGE', KEYS[6], 0, -1)) do
+        table.insert(levels, tonumber(priority))
+        level_keys[tonumber(priority)] = ARGV[2] .. priority
+    end
+    for _, element in ipairs(due_elements) do
+        local level = tonumber(cjson.decode(element)['priority']) or 0
+        if not due_by_level[level] then
+            due_by_level[level] = {}
+            if not level_keys[level] then
+                table.insert(levels, level)
+            end
+        end
+        table.insert(due_by_level[level], element)
+    end
+    table.sort(levels, function(a, b) return a > b end)
+
+    for _, level in ipairs(levels) do
+        if level_keys[level] and visit_list(level_keys[level]) then
+            break
+        end
+        if due_by_level[level] and visit_all(due_by_level[level]) then
+            break
+        end
+    end
+else
+    local element_id = redis.call('GET', KEYS[6])
+    local full = false
+    while not full and element_id and element_id ~= cjson.null do
+        local element = redis.call('GET', ARGV[2] .. element_id)
+        if not element then
+            return redis.error_reply('CORRUPTED element ' .. element_id .. ' is missing')
+        end
+        full = visit(element)
+        element_id = cjson.decode(element)['next']
+    end
+    if not full then
+        visit_all(due_elements)
+    end
+end
+return elements
+"#;
+
+pub fn peek_elements_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(PEEK_ELEMENTS_SCRIPT))
+}
+
+// Pushes an element unless its dedup key was claimed within the dedup window, and claims the key
+// for it in the same call. Returns the id of the element the key was claimed with.
+// KEYS[#KEYS]: dedup key
+// ARGV[5]: element, ARGV[6]: dedup window in milliseconds
+const PUSH_DEDUP_ELEMENT_SCRIPT: &str = r#"
+local dedup_key = KEYS[#KEYS]
+local original_element_id = redis.call('GET', dedup_key)
+if original_element_id then
+    return original_element_id
+end
+
+local element = cjson.decode(ARGV[5])
+local corrupted = push_back(element)
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+
+redis.call('SET', dedup_key, element['id'], 'PX', ARGV[6])
+redis.call('PUBLISH', ARGV[3], element['id'])
+return element['id']
+"#;
+
+pub fn push_dedup_element_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(PUSH_DEDUP_ELEMENT_SCRIPT))
+}
+
+// Moves a dead letter to the end of the queue with its delivery attempts reset.
+// ARGV[5]: element id
+const REQUEUE_DEAD_LETTER_SCRIPT: &str = r#"
+local element = redis.call('HGET', KEYS[3], ARGV[5])
+if not element then
+    return 0
+end
+
+element = cjson.decode(element)
+element['redeliveries'] = 0
+element['expired'] = nil
+local corrupted = push_back(element)
+if corrupted then
+    return redis.error_reply(corrupted)
+end
+
+redis.call('HDEL', KEYS[3], ARGV[5])
+redis.call('PUBLISH', ARGV[3], ARGV[5])
+return 1
+"#;
+
+pub fn requeue_dead_letter_script() -> &'static Script {
+    static SCRIPT: OnceLock<Script> = OnceLock::new();
+    SCRIPT.get_or_init(|| queue_script(REQUEUE_DEAD_LETTER_SCRIPT))
+}
+
+/// Starts an invocation of one of the scripts that work on the whole queue state, with the keys
+/// and arguments they share.
+pub fn queue_invocation(
+    script: &'static Script,
+    queue_keys: &QueueKeys,
+    backend: QueueBackend,
+    max_attempts: Option<u32>,
+) -> ScriptInvocation<'static> {
+    let mut invocation = script.key(queue_keys.processing());
+    invocation
+        .key(queue_keys.processing_deadlines())
+        .key(queue_keys.dead_letters())
+        .key(queue_keys.delayed())
+        .key(queue_keys.length());
+    match backend {
+        QueueBackend::List => invocation
+            .key(queue_keys.list())
+            .arg("list")
+            .arg(queue_keys.element_prefix()),
+        QueueBackend::Priority => invocation
+            .key(queue_keys.priorities())
+            .arg("priority")
+            .arg(queue_keys.priority_prefix()),
+        QueueBackend::Stream => invocation
+            .key(queue_keys.stream())
+            .arg("stream")
+            .arg(queue_keys.element_prefix()),
+        QueueBackend::LinkedList => invocation
+            .key(queue_keys.first_element())
+            .key(queue_keys.last_element())
+            .arg("linked_list")
+            .arg(queue_keys.element_prefix()),
+    };
+    invocation
+        .arg(queue_keys.push_channel())
+        .arg(max_attempts.map(|max_attempts| max_attempts.to_string()).unwrap_or_default());
+    invocation
+}
@@ -0,0 +1,20 @@
+/// Consumer group, and consumer within it, that a queue reads its stream as.
+#[derive(Clone)]
+pub struct StreamConsumer {
+    group: String,
+    consumer: String,
+}
+
+impl StreamConsumer {
+    pub fn new(group: String, consumer: String) -> Self {
+        StreamConsumer { group, consumer }
+    }
+
+    pub fn get_group(&self) -> String {
+        self.group.clone()
+    }
+
+    pub fn get_consumer(&self) -> String {
+        self.consumer.clone()
+    }
+}
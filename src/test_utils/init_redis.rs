@@ -20,3 +20,25 @@ pub async fn initialize_async_redis() -> redis::aio::MultiplexedConnection {
         .await
         .unwrap()
 }
+
+/// Independent lock nodes, one server process each, listening on the ports in
+/// `REDIS_LOCK_PORTS`.
+#[allow(dead_code)]
+pub fn initialize_redis_clients(nodes: usize) -> Vec<redis::Client> {
+    let host = env::var("REDIS_HOST").unwrap_or("127.0.0.1".to_string());
+    let ports = env::var("REDIS_LOCK_PORTS").unwrap_or("6380,6381,6382".to_string());
+
+    let redis_clients: Vec<redis::Client> = ports
+        .split(',')
+        .take(nodes)
+        .map(|port| redis::Client::open(format!("redis://{}:{}", host, port.trim())).unwrap())
+        .collect();
+    assert_eq!(redis_clients.len(), nodes, "REDIS_LOCK_PORTS lists too few nodes");
+    redis_clients
+}
+
+/// A lock node that is down, nothing listens on its port.
+#[allow(dead_code)]
+pub fn initialize_unreachable_redis_client() -> redis::Client {
+    redis::Client::open("redis://127.0.0.1:1").unwrap()
+}